@@ -0,0 +1,481 @@
+//! The `AsyncFileIoExt` trait, and related utilities and impls.
+
+use crate::fs::{Advice, FileIoExt};
+use io_lifetimes::AsFilelike;
+use std::future::Future;
+use std::io::{self, IoSliceMut};
+
+/// Extension trait for `async_std::fs::File`, `cap_async_std::fs::File`, and
+/// `tokio::fs::File`.
+///
+/// This is the async counterpart of [`FileIoExt`], with the same semantics.
+/// Each operation runs the synchronous [`FileIoExt`] function on a
+/// duplicate of the file handle in the runtime's `spawn_blocking`, so data
+/// is copied through an intermediate buffer.
+///
+/// Since async-std files buffer writes internally, each operation on them
+/// first flushes any pending writes made through the file's `Write`
+/// implementation. Tokio files can only be flushed through a `&mut`
+/// reference, so writes made through their `AsyncWrite` implementation must
+/// be flushed before using these functions.
+pub trait AsyncFileIoExt {
+    /// Announce the expected access pattern of the data at the given offset.
+    fn advise(
+        &self,
+        offset: u64,
+        len: u64,
+        advice: Advice,
+    ) -> impl Future<Output = io::Result<()>> + Send;
+
+    /// Allocate space in the file, increasing the file size as needed, and
+    /// ensuring that there are no holes under the given range.
+    fn allocate(&self, offset: u64, len: u64) -> impl Future<Output = io::Result<()>> + Send;
+
+    /// Reads a number of bytes starting from a given offset.
+    ///
+    /// This is similar to [`FileIoExt::read_at`].
+    fn read_at(
+        &self,
+        buf: &mut [u8],
+        offset: u64,
+    ) -> impl Future<Output = io::Result<usize>> + Send;
+
+    /// Reads the exact number of byte required to fill buf from the given
+    /// offset.
+    ///
+    /// This is similar to [`FileIoExt::read_exact_at`].
+    fn read_exact_at(
+        &self,
+        buf: &mut [u8],
+        offset: u64,
+    ) -> impl Future<Output = io::Result<()>> + Send;
+
+    /// Is to `read_exact_at` what `read_vectored_at` is to `read_at`.
+    ///
+    /// This is similar to [`FileIoExt::read_exact_vectored_at`].
+    fn read_exact_vectored_at(
+        &self,
+        bufs: &mut [IoSliceMut<'_>],
+        offset: u64,
+    ) -> impl Future<Output = io::Result<()>> + Send;
+
+    /// Read all bytes, starting at `offset`, until EOF in this source,
+    /// placing them into `buf`.
+    ///
+    /// This is similar to [`FileIoExt::read_to_end_at`].
+    fn read_to_end_at(
+        &self,
+        buf: &mut Vec<u8>,
+        offset: u64,
+    ) -> impl Future<Output = io::Result<usize>> + Send;
+
+    /// Writes a number of bytes starting from a given offset.
+    ///
+    /// This is similar to [`FileIoExt::write_at`].
+    fn write_at(&self, buf: &[u8], offset: u64) -> impl Future<Output = io::Result<usize>> + Send;
+
+    /// Attempts to write an entire buffer starting from a given offset.
+    ///
+    /// This is similar to [`FileIoExt::write_all_at`].
+    fn write_all_at(&self, buf: &[u8], offset: u64) -> impl Future<Output = io::Result<()>> + Send;
+
+    /// Writes a number of bytes at the end of a file.
+    ///
+    /// This is similar to [`FileIoExt::append`], and likewise leaves the
+    /// current position of the file unmodified.
+    fn append(&self, buf: &[u8]) -> impl Future<Output = io::Result<usize>> + Send;
+}
+
+#[cfg(feature = "async-std")]
+impl AsyncFileIoExt for async_std::fs::File {
+    #[inline]
+    async fn advise(&self, offset: u64, len: u64, advice: Advice) -> io::Result<()> {
+        advise(self, offset, len, advice).await
+    }
+
+    #[inline]
+    async fn allocate(&self, offset: u64, len: u64) -> io::Result<()> {
+        allocate(self, offset, len).await
+    }
+
+    #[inline]
+    async fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        read_at(self, buf, offset).await
+    }
+
+    #[inline]
+    async fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        read_exact_at(self, buf, offset).await
+    }
+
+    #[inline]
+    async fn read_exact_vectored_at(
+        &self,
+        bufs: &mut [IoSliceMut<'_>],
+        offset: u64,
+    ) -> io::Result<()> {
+        read_exact_vectored_at(self, bufs, offset).await
+    }
+
+    #[inline]
+    async fn read_to_end_at(&self, buf: &mut Vec<u8>, offset: u64) -> io::Result<usize> {
+        read_to_end_at(self, buf, offset).await
+    }
+
+    #[inline]
+    async fn write_at(&self, buf: &[u8], offset: u64) -> io::Result<usize> {
+        write_at(self, buf, offset).await
+    }
+
+    #[inline]
+    async fn write_all_at(&self, buf: &[u8], offset: u64) -> io::Result<()> {
+        write_all_at(self, buf, offset).await
+    }
+
+    #[inline]
+    async fn append(&self, buf: &[u8]) -> io::Result<usize> {
+        append(self, buf).await
+    }
+}
+
+#[cfg(feature = "cap_async_std_impls")]
+impl AsyncFileIoExt for cap_async_std::fs::File {
+    #[inline]
+    async fn advise(&self, offset: u64, len: u64, advice: Advice) -> io::Result<()> {
+        advise(self, offset, len, advice).await
+    }
+
+    #[inline]
+    async fn allocate(&self, offset: u64, len: u64) -> io::Result<()> {
+        allocate(self, offset, len).await
+    }
+
+    #[inline]
+    async fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        read_at(self, buf, offset).await
+    }
+
+    #[inline]
+    async fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        read_exact_at(self, buf, offset).await
+    }
+
+    #[inline]
+    async fn read_exact_vectored_at(
+        &self,
+        bufs: &mut [IoSliceMut<'_>],
+        offset: u64,
+    ) -> io::Result<()> {
+        read_exact_vectored_at(self, bufs, offset).await
+    }
+
+    #[inline]
+    async fn read_to_end_at(&self, buf: &mut Vec<u8>, offset: u64) -> io::Result<usize> {
+        read_to_end_at(self, buf, offset).await
+    }
+
+    #[inline]
+    async fn write_at(&self, buf: &[u8], offset: u64) -> io::Result<usize> {
+        write_at(self, buf, offset).await
+    }
+
+    #[inline]
+    async fn write_all_at(&self, buf: &[u8], offset: u64) -> io::Result<()> {
+        write_all_at(self, buf, offset).await
+    }
+
+    #[inline]
+    async fn append(&self, buf: &[u8]) -> io::Result<usize> {
+        append(self, buf).await
+    }
+}
+
+#[cfg(feature = "cap_async_std_impls_fs_utf8")]
+impl AsyncFileIoExt for cap_async_std::fs_utf8::File {
+    #[inline]
+    async fn advise(&self, offset: u64, len: u64, advice: Advice) -> io::Result<()> {
+        advise(self, offset, len, advice).await
+    }
+
+    #[inline]
+    async fn allocate(&self, offset: u64, len: u64) -> io::Result<()> {
+        allocate(self, offset, len).await
+    }
+
+    #[inline]
+    async fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        read_at(self, buf, offset).await
+    }
+
+    #[inline]
+    async fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        read_exact_at(self, buf, offset).await
+    }
+
+    #[inline]
+    async fn read_exact_vectored_at(
+        &self,
+        bufs: &mut [IoSliceMut<'_>],
+        offset: u64,
+    ) -> io::Result<()> {
+        read_exact_vectored_at(self, bufs, offset).await
+    }
+
+    #[inline]
+    async fn read_to_end_at(&self, buf: &mut Vec<u8>, offset: u64) -> io::Result<usize> {
+        read_to_end_at(self, buf, offset).await
+    }
+
+    #[inline]
+    async fn write_at(&self, buf: &[u8], offset: u64) -> io::Result<usize> {
+        write_at(self, buf, offset).await
+    }
+
+    #[inline]
+    async fn write_all_at(&self, buf: &[u8], offset: u64) -> io::Result<()> {
+        write_all_at(self, buf, offset).await
+    }
+
+    #[inline]
+    async fn append(&self, buf: &[u8]) -> io::Result<usize> {
+        append(self, buf).await
+    }
+}
+
+#[cfg(feature = "tokio_impls")]
+impl AsyncFileIoExt for tokio::fs::File {
+    #[inline]
+    async fn advise(&self, offset: u64, len: u64, advice: Advice) -> io::Result<()> {
+        advise(self, offset, len, advice).await
+    }
+
+    #[inline]
+    async fn allocate(&self, offset: u64, len: u64) -> io::Result<()> {
+        allocate(self, offset, len).await
+    }
+
+    #[inline]
+    async fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        read_at(self, buf, offset).await
+    }
+
+    #[inline]
+    async fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        read_exact_at(self, buf, offset).await
+    }
+
+    #[inline]
+    async fn read_exact_vectored_at(
+        &self,
+        bufs: &mut [IoSliceMut<'_>],
+        offset: u64,
+    ) -> io::Result<()> {
+        read_exact_vectored_at(self, bufs, offset).await
+    }
+
+    #[inline]
+    async fn read_to_end_at(&self, buf: &mut Vec<u8>, offset: u64) -> io::Result<usize> {
+        read_to_end_at(self, buf, offset).await
+    }
+
+    #[inline]
+    async fn write_at(&self, buf: &[u8], offset: u64) -> io::Result<usize> {
+        write_at(self, buf, offset).await
+    }
+
+    #[inline]
+    async fn write_all_at(&self, buf: &[u8], offset: u64) -> io::Result<()> {
+        write_all_at(self, buf, offset).await
+    }
+
+    #[inline]
+    async fn append(&self, buf: &[u8]) -> io::Result<usize> {
+        append(self, buf).await
+    }
+}
+
+/// A file type whose runtime can run blocking operations.
+trait BlockingFile: AsFilelike + Sync {
+    /// Flush any writes buffered by the file.
+    fn flush_buffered(&self) -> impl Future<Output = io::Result<()>> + Send;
+
+    /// Run `f` in a thread where it's ok to block.
+    fn spawn_blocking<F, T>(f: F) -> impl Future<Output = io::Result<T>> + Send
+    where
+        F: FnOnce() -> io::Result<T> + Send + 'static,
+        T: Send + 'static;
+}
+
+#[cfg(feature = "async-std")]
+impl BlockingFile for async_std::fs::File {
+    async fn flush_buffered(&self) -> io::Result<()> {
+        async_std::io::WriteExt::flush(&mut &*self).await
+    }
+
+    async fn spawn_blocking<F, T>(f: F) -> io::Result<T>
+    where
+        F: FnOnce() -> io::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        async_std::task::spawn_blocking(f).await
+    }
+}
+
+#[cfg(feature = "cap_async_std_impls")]
+impl BlockingFile for cap_async_std::fs::File {
+    async fn flush_buffered(&self) -> io::Result<()> {
+        async_std::io::WriteExt::flush(&mut &*self).await
+    }
+
+    async fn spawn_blocking<F, T>(f: F) -> io::Result<T>
+    where
+        F: FnOnce() -> io::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        async_std::task::spawn_blocking(f).await
+    }
+}
+
+#[cfg(feature = "cap_async_std_impls_fs_utf8")]
+impl BlockingFile for cap_async_std::fs_utf8::File {
+    async fn flush_buffered(&self) -> io::Result<()> {
+        async_std::io::WriteExt::flush(&mut &*self).await
+    }
+
+    async fn spawn_blocking<F, T>(f: F) -> io::Result<T>
+    where
+        F: FnOnce() -> io::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        async_std::task::spawn_blocking(f).await
+    }
+}
+
+#[cfg(feature = "tokio_impls")]
+impl BlockingFile for tokio::fs::File {
+    async fn flush_buffered(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    async fn spawn_blocking<F, T>(f: F) -> io::Result<T>
+    where
+        F: FnOnce() -> io::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        match tokio::task::spawn_blocking(f).await {
+            Ok(result) => result,
+            Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+            Err(err) => Err(io::Error::other(err)),
+        }
+    }
+}
+
+/// Flush any writes buffered by `file`, and then run `f` on a duplicate of
+/// its handle in a blocking-capable thread.
+async fn blocking<File, F, T>(file: &File, f: F) -> io::Result<T>
+where
+    File: BlockingFile,
+    F: FnOnce(&std::fs::File) -> io::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    file.flush_buffered().await?;
+    let dup = file.as_filelike_view::<std::fs::File>().try_clone()?;
+    File::spawn_blocking(move || f(&dup)).await
+}
+
+async fn advise<File: BlockingFile>(
+    file: &File,
+    offset: u64,
+    len: u64,
+    advice: Advice,
+) -> io::Result<()> {
+    blocking(file, move |file| file.advise(offset, len, advice)).await
+}
+
+async fn allocate<File: BlockingFile>(file: &File, offset: u64, len: u64) -> io::Result<()> {
+    blocking(file, move |file| file.allocate(offset, len)).await
+}
+
+async fn read_at<File: BlockingFile>(
+    file: &File,
+    buf: &mut [u8],
+    offset: u64,
+) -> io::Result<usize> {
+    let len = buf.len();
+    let data = blocking(file, move |file| {
+        let mut data = vec![0; len];
+        let n = file.read_at(&mut data, offset)?;
+        data.truncate(n);
+        Ok(data)
+    })
+    .await?;
+    buf[..data.len()].copy_from_slice(&data);
+    Ok(data.len())
+}
+
+async fn read_exact_at<File: BlockingFile>(
+    file: &File,
+    buf: &mut [u8],
+    offset: u64,
+) -> io::Result<()> {
+    let len = buf.len();
+    let data = blocking(file, move |file| {
+        let mut data = vec![0; len];
+        file.read_exact_at(&mut data, offset)?;
+        Ok(data)
+    })
+    .await?;
+    buf.copy_from_slice(&data);
+    Ok(())
+}
+
+async fn read_exact_vectored_at<File: BlockingFile>(
+    file: &File,
+    bufs: &mut [IoSliceMut<'_>],
+    offset: u64,
+) -> io::Result<()> {
+    let len = bufs.iter().map(|buf| buf.len()).sum();
+    let data = blocking(file, move |file| {
+        let mut data = vec![0; len];
+        file.read_exact_at(&mut data, offset)?;
+        Ok(data)
+    })
+    .await?;
+    let mut data = &data[..];
+    for buf in bufs {
+        let (head, tail) = data.split_at(buf.len());
+        buf.copy_from_slice(head);
+        data = tail;
+    }
+    Ok(())
+}
+
+async fn read_to_end_at<File: BlockingFile>(
+    file: &File,
+    buf: &mut Vec<u8>,
+    offset: u64,
+) -> io::Result<usize> {
+    let data = blocking(file, move |file| {
+        let mut data = Vec::new();
+        file.read_to_end_at(&mut data, offset)?;
+        Ok(data)
+    })
+    .await?;
+    buf.extend_from_slice(&data);
+    Ok(data.len())
+}
+
+async fn write_at<File: BlockingFile>(file: &File, buf: &[u8], offset: u64) -> io::Result<usize> {
+    let data = buf.to_vec();
+    blocking(file, move |file| file.write_at(&data, offset)).await
+}
+
+async fn write_all_at<File: BlockingFile>(file: &File, buf: &[u8], offset: u64) -> io::Result<()> {
+    let data = buf.to_vec();
+    blocking(file, move |file| file.write_all_at(&data, offset)).await
+}
+
+async fn append<File: BlockingFile>(file: &File, buf: &[u8]) -> io::Result<usize> {
+    let data = buf.to_vec();
+    blocking(file, move |file| file.append(&data)).await
+}
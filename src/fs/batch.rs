@@ -0,0 +1,366 @@
+//! The `Batch` type, for submitting positional I/O in batches.
+
+use crate::fs::FileIoExt;
+use io_lifetimes::{AsFilelike, BorrowedFilelike};
+use io_uring::{opcode, squeue, types, IoUring};
+use rustix::fd::AsRawFd;
+use rustix::io::{Errno, ReadWriteFlags};
+use std::cell::RefCell;
+use std::io::{self, IoSlice};
+
+/// The number of entries in each thread's cached io_uring.
+const CACHED_RING_ENTRIES: u32 = 64;
+
+/// The number of times in a row `submit_and_wait` may fail, with operations
+/// in flight, before `submit_to_ring` aborts.
+const MAX_SUBMIT_FAILURES: u32 = 8;
+
+thread_local! {
+    /// The io_uring used by `Batch::with_cached_ring` on this thread: `None`
+    /// if it hasn't been created yet, and `Some(None)` if io_uring is
+    /// unavailable.
+    static CACHED_RING: RefCell<Option<Option<IoUring>>> = const { RefCell::new(None) };
+}
+
+/// A queued operation, together with the handle and buffers it borrows.
+enum Op<'a> {
+    ReadAt(BorrowedFilelike<'a>, &'a mut [u8], u64),
+    WriteVectoredAt(BorrowedFilelike<'a>, &'a [IoSlice<'a>], u64),
+    Append(BorrowedFilelike<'a>, &'a [u8]),
+    Allocate(BorrowedFilelike<'a>, u64, u64),
+}
+
+impl Op<'_> {
+    /// Build the io_uring submission entry for this operation, tagged with
+    /// `index`.
+    fn entry(&mut self, index: usize) -> squeue::Entry {
+        let entry = match self {
+            Self::ReadAt(fd, buf, offset) => opcode::Read::new(
+                types::Fd(fd.as_raw_fd()),
+                buf.as_mut_ptr(),
+                buf.len().min(u32::MAX as usize) as u32,
+            )
+            .offset(*offset)
+            .build(),
+            Self::WriteVectoredAt(fd, bufs, offset) => opcode::Writev::new(
+                types::Fd(fd.as_raw_fd()),
+                bufs.as_ptr().cast(),
+                bufs.len().min(u32::MAX as usize) as u32,
+            )
+            .offset(*offset)
+            .build(),
+            Self::Append(fd, buf) => opcode::Write::new(
+                types::Fd(fd.as_raw_fd()),
+                buf.as_ptr(),
+                buf.len().min(u32::MAX as usize) as u32,
+            )
+            .rw_flags(ReadWriteFlags::APPEND.bits() as i32)
+            .build(),
+            Self::Allocate(fd, offset, len) => {
+                opcode::Fallocate::new(types::Fd(fd.as_raw_fd()), *len)
+                    .offset(*offset)
+                    .build()
+            }
+        };
+        entry.user_data(index as u64)
+    }
+
+    /// Perform this operation synchronously with [`FileIoExt`].
+    fn run(&mut self) -> io::Result<usize> {
+        match self {
+            Self::ReadAt(fd, buf, offset) => fd.read_at(buf, *offset),
+            Self::WriteVectoredAt(fd, bufs, offset) => fd.write_vectored_at(bufs, *offset),
+            Self::Append(fd, buf) => fd.append(buf),
+            Self::Allocate(fd, offset, len) => fd.allocate(*offset, *len).map(|()| 0),
+        }
+    }
+}
+
+/// A queue of positional I/O operations which are submitted together.
+///
+/// Operations are queued with [`Batch::read_at`],
+/// [`Batch::write_vectored_at`], [`Batch::append`], and
+/// [`Batch::allocate`], and performed by [`Batch::submit`]. With
+/// [io_uring], a whole batch costs a small number of system calls, rather
+/// than one per operation.
+///
+/// When io_uring is unavailable, for example because it's disabled or
+/// blocked by a seccomp filter, or when the kernel doesn't support a
+/// particular operation, operations are performed one at a time with the
+/// [`FileIoExt`] functions instead, so results are the same either way.
+///
+/// Operations in a batch may be performed in any order, and concurrently,
+/// so they shouldn't depend on each other's effects.
+///
+/// This is currently only available on Linux and Android, with the
+/// `use_io_uring` feature.
+///
+/// [io_uring]: https://man7.org/linux/man-pages/man7/io_uring.7.html
+pub struct Batch<'a> {
+    ring: Option<IoUring>,
+    ops: Vec<Op<'a>>,
+}
+
+impl<'a> Batch<'a> {
+    /// Create a new `Batch`, using an io_uring with room for `entries`
+    /// operations in flight at a time if it's available.
+    ///
+    /// Larger batches are submitted in several rounds. `entries` must be
+    /// non-zero.
+    pub fn new(entries: u32) -> io::Result<Self> {
+        let ring = match IoUring::new(entries) {
+            Ok(ring) => Some(ring),
+            Err(err) if is_unavailable(&err) => None,
+            Err(err) => return Err(err),
+        };
+        Ok(Self {
+            ring,
+            ops: Vec::new(),
+        })
+    }
+
+    /// Create a new `Batch` which doesn't use io_uring, and performs
+    /// operations one at a time.
+    pub fn without_io_uring() -> Self {
+        Self {
+            ring: None,
+            ops: Vec::new(),
+        }
+    }
+
+    /// Call `f` with an empty `Batch` which uses this thread's cached
+    /// io_uring, creating it if need be, so that frequent small batches
+    /// don't each pay for setting up a ring.
+    ///
+    /// Returns `None` without calling `f` if io_uring is unavailable.
+    pub(crate) fn with_cached_ring<R>(f: impl FnOnce(&mut Batch<'a>) -> R) -> Option<R> {
+        let ring = CACHED_RING
+            .try_with(|cached| {
+                let mut cached = cached.borrow_mut();
+                match cached.take() {
+                    Some(Some(ring)) => Some(ring),
+                    Some(None) => {
+                        *cached = Some(None);
+                        None
+                    }
+                    None => match IoUring::new(CACHED_RING_ENTRIES) {
+                        Ok(ring) => Some(ring),
+                        Err(err) => {
+                            if is_unavailable(&err) {
+                                *cached = Some(None);
+                            }
+                            None
+                        }
+                    },
+                }
+            })
+            .ok()??;
+
+        let mut batch = Self {
+            ring: Some(ring),
+            ops: Vec::new(),
+        };
+        let result = f(&mut batch);
+
+        // Put the ring back for next time, unless it failed.
+        if let Some(ring) = batch.ring.take() {
+            let _ = CACHED_RING.try_with(|cached| *cached.borrow_mut() = Some(Some(ring)));
+        }
+        Some(result)
+    }
+
+    /// Test whether this `Batch` is using io_uring.
+    #[inline]
+    pub fn uses_io_uring(&self) -> bool {
+        self.ring.is_some()
+    }
+
+    /// Return the number of queued operations.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Test whether there are no queued operations.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Queue a [`FileIoExt::read_at`], and return its index in the results
+    /// of the next [`Batch::submit`].
+    pub fn read_at<Filelike: AsFilelike + FileIoExt>(
+        &mut self,
+        file: &'a Filelike,
+        buf: &'a mut [u8],
+        offset: u64,
+    ) -> usize {
+        self.push(Op::ReadAt(file.as_filelike(), buf, offset))
+    }
+
+    /// Queue a [`FileIoExt::write_vectored_at`], and return its index in the
+    /// results of the next [`Batch::submit`].
+    pub fn write_vectored_at<Filelike: AsFilelike + FileIoExt>(
+        &mut self,
+        file: &'a Filelike,
+        bufs: &'a [IoSlice<'a>],
+        offset: u64,
+    ) -> usize {
+        self.push(Op::WriteVectoredAt(file.as_filelike(), bufs, offset))
+    }
+
+    /// Queue a [`FileIoExt::append`], and return its index in the results of
+    /// the next [`Batch::submit`].
+    pub fn append<Filelike: AsFilelike + FileIoExt>(
+        &mut self,
+        file: &'a Filelike,
+        buf: &'a [u8],
+    ) -> usize {
+        self.push(Op::Append(file.as_filelike(), buf))
+    }
+
+    /// Queue a [`FileIoExt::allocate`], and return its index in the results
+    /// of the next [`Batch::submit`], where it's reported as `Ok(0)` if it
+    /// succeeds.
+    pub fn allocate<Filelike: AsFilelike + FileIoExt>(
+        &mut self,
+        file: &'a Filelike,
+        offset: u64,
+        len: u64,
+    ) -> usize {
+        self.push(Op::Allocate(file.as_filelike(), offset, len))
+    }
+
+    fn push(&mut self, op: Op<'a>) -> usize {
+        self.ops.push(op);
+        self.ops.len() - 1
+    }
+
+    /// Perform all the queued operations, wait for them to complete, and
+    /// return their results in the order they were queued.
+    ///
+    /// As with the corresponding [`FileIoExt`] functions, reads and writes
+    /// may be short. The queue is empty afterwards.
+    ///
+    /// This only fails if the io_uring itself fails before the kernel has
+    /// started any of the queued operations, in which case they're all
+    /// discarded, none of them are in flight, and later batches perform
+    /// operations one at a time.
+    ///
+    /// If the io_uring fails once operations are in flight, the kernel may
+    /// still be using their buffers, so this keeps waiting for them. If it
+    /// can't wait for them, because the ring is broken or keeps failing, it
+    /// aborts the process rather than returning and releasing the buffers.
+    pub fn submit(&mut self) -> io::Result<Vec<io::Result<usize>>> {
+        let mut results = Vec::with_capacity(self.ops.len());
+        results.resize_with(self.ops.len(), || None);
+
+        if let Some(ring) = &mut self.ring {
+            if let Err(err) = submit_to_ring(ring, &mut self.ops, &mut results) {
+                self.ring = None;
+                self.ops.clear();
+                return Err(err);
+            }
+        }
+
+        let results = self
+            .ops
+            .drain(..)
+            .zip(results)
+            .map(|(mut op, result)| match result {
+                Some(result) => result,
+                None => op.run(),
+            })
+            .collect();
+        Ok(results)
+    }
+}
+
+/// Submit `ops` to `ring` and wait for them all to complete, recording the
+/// results in `results`.
+///
+/// Operations which the kernel doesn't support are left as `None`.
+fn submit_to_ring(
+    ring: &mut IoUring,
+    ops: &mut [Op<'_>],
+    results: &mut [Option<io::Result<usize>>],
+) -> io::Result<()> {
+    let mut next = 0;
+    let mut in_flight = 0;
+    let mut failures = 0;
+    while next < ops.len() || in_flight != 0 {
+        {
+            let mut sq = ring.submission();
+            while next < ops.len() && !sq.is_full() {
+                let entry = ops[next].entry(next);
+                // SAFETY: The buffers are borrowed by `ops` for `'a`, and we
+                // don't return until the kernel is done with them.
+                unsafe { sq.push(&entry) }.unwrap();
+                next += 1;
+                in_flight += 1;
+            }
+        }
+
+        match ring.submit_and_wait(1) {
+            Ok(_) => failures = 0,
+            Err(err)
+                if err.kind() == io::ErrorKind::Interrupted
+                    || err.kind() == io::ErrorKind::WouldBlock
+                    || err.raw_os_error() == Some(Errno::BUSY.raw_os_error()) => {}
+            Err(err) => {
+                // If the kernel hasn't consumed any of the entries, nothing
+                // is in flight, and it's safe to give up. Otherwise, keep
+                // waiting for the operations it has, so that their buffers
+                // aren't released too early, unless the ring itself is
+                // broken, or keeps failing, in which case waiting won't help.
+                if in_flight == ring.submission().len() {
+                    return Err(err);
+                }
+                failures += 1;
+                if is_fatal(&err) || failures >= MAX_SUBMIT_FAILURES {
+                    abort_in_flight(&err);
+                }
+            }
+        }
+
+        for cqe in ring.completion() {
+            let index = cqe.user_data() as usize;
+            in_flight -= 1;
+            results[index] = match cqe.result() {
+                res if res >= 0 => Some(Ok(res as usize)),
+                res => match Errno::from_raw_os_error(-res) {
+                    Errno::INVAL | Errno::OPNOTSUPP => None,
+                    errno => Some(Err(errno.into())),
+                },
+            };
+        }
+    }
+    Ok(())
+}
+
+/// Abort the process, because operations which borrow the caller's buffers
+/// are in flight on a ring which can't be waited on.
+///
+/// Returning, or unwinding, would let the caller free or reuse buffers which
+/// the kernel may still read from or write to.
+#[cold]
+fn abort_in_flight(err: &io::Error) -> ! {
+    eprintln!("io_uring failed with operations in flight: {}", err);
+    std::process::abort()
+}
+
+/// Test whether an error from `submit_and_wait` means that the ring can't be
+/// used any more.
+fn is_fatal(err: &io::Error) -> bool {
+    [Errno::BADF, Errno::FAULT, Errno::NXIO, Errno::OPNOTSUPP]
+        .iter()
+        .any(|errno| err.raw_os_error() == Some(errno.raw_os_error()))
+}
+
+/// Test whether an error from creating an io_uring means that io_uring is
+/// unavailable, rather than that something else went wrong.
+fn is_unavailable(err: &io::Error) -> bool {
+    [Errno::NOSYS, Errno::PERM, Errno::ACCESS]
+        .iter()
+        .any(|errno| err.raw_os_error() == Some(errno.raw_os_error()))
+}
@@ -0,0 +1,210 @@
+//! The `AlignedBuf` type, and related utilities for direct I/O.
+
+use io_lifetimes::AsFilelike;
+use std::alloc::{alloc_zeroed, dealloc, handle_alloc_error, Layout};
+use std::fmt;
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::ptr::{self, NonNull};
+
+/// The alignment to assume when the platform can't report one.
+const DEFAULT_ALIGNMENT: usize = 4096;
+
+/// A zero-initialized byte buffer whose address and length are multiples of
+/// an alignment, for use with direct I/O.
+///
+/// Direct I/O, enabled with [`FdFlags::DIRECT`], bypasses the page cache,
+/// and typically requires buffer addresses, lengths, and file offsets to be
+/// multiples of the device's logical block size. Use
+/// [`AlignedBuf::for_direct_io`] to create a buffer which meets the
+/// requirements of a particular file, and [`FileIoExt::read_at_aligned`] and
+/// [`FileIoExt::write_at_aligned`] to check offsets.
+///
+/// [`FdFlags::DIRECT`]: crate::fs::FdFlags::DIRECT
+/// [`FileIoExt::read_at_aligned`]: crate::fs::FileIoExt::read_at_aligned
+/// [`FileIoExt::write_at_aligned`]: crate::fs::FileIoExt::write_at_aligned
+pub struct AlignedBuf {
+    ptr: NonNull<u8>,
+    len: usize,
+    align: usize,
+}
+
+// SAFETY: `AlignedBuf` owns its allocation, like a `Vec<u8>`.
+unsafe impl Send for AlignedBuf {}
+unsafe impl Sync for AlignedBuf {}
+
+impl AlignedBuf {
+    /// Allocate a buffer of `len` bytes, aligned to `align`.
+    ///
+    /// `align` must be a power of two, and `len` must be a multiple of it.
+    pub fn new(len: usize, align: usize) -> io::Result<Self> {
+        if !align.is_power_of_two() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "buffer alignment must be a power of two",
+            ));
+        }
+        if len & (align - 1) != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "buffer length {} is not a multiple of the alignment {}",
+                    len, align
+                ),
+            ));
+        }
+        if len == 0 {
+            return Ok(Self {
+                // An empty buffer needs no allocation, but its address must
+                // still be aligned, so use a dangling pointer at `align`.
+                // SAFETY: `align` is a power of two, so it's non-zero.
+                ptr: unsafe { NonNull::new_unchecked(ptr::null_mut::<u8>().wrapping_add(align)) },
+                len,
+                align,
+            });
+        }
+        let layout = Layout::from_size_align(len, align)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "buffer is too large"))?;
+        // SAFETY: `layout` has a non-zero size.
+        let ptr = unsafe { alloc_zeroed(layout) };
+        let ptr = NonNull::new(ptr).unwrap_or_else(|| handle_alloc_error(layout));
+        Ok(Self { ptr, len, align })
+    }
+
+    /// Allocate a buffer of at least `len` bytes, with the alignment that
+    /// [`direct_io_alignment`] reports for `file`, rounding `len` up to a
+    /// multiple of it.
+    pub fn for_direct_io<Filelike: AsFilelike>(file: &Filelike, len: usize) -> io::Result<Self> {
+        let align = direct_io_alignment(file)?;
+        let len = len
+            .checked_next_multiple_of(align)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "buffer is too large"))?;
+        Self::new(len, align)
+    }
+
+    /// Return the alignment of the buffer.
+    #[inline]
+    pub fn align(&self) -> usize {
+        self.align
+    }
+}
+
+impl Deref for AlignedBuf {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        // SAFETY: `ptr` points to `len` initialized bytes which we own.
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for AlignedBuf {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: `ptr` points to `len` initialized bytes which we own.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for AlignedBuf {
+    fn drop(&mut self) {
+        if self.len != 0 {
+            // SAFETY: The buffer was allocated with this layout in `new`.
+            unsafe {
+                dealloc(
+                    self.ptr.as_ptr(),
+                    Layout::from_size_align_unchecked(self.len, self.align),
+                )
+            }
+        }
+    }
+}
+
+impl fmt::Debug for AlignedBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AlignedBuf")
+            .field("len", &self.len)
+            .field("align", &self.align)
+            .finish()
+    }
+}
+
+/// Check that `offset` meets `buf`'s alignment, for direct I/O.
+pub(crate) fn check_offset(buf: &AlignedBuf, offset: u64) -> io::Result<()> {
+    // Alignments are powers of two, so this tests for a multiple.
+    if offset & (buf.align() as u64 - 1) != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "direct I/O offset {} is not a multiple of the alignment {}",
+                offset,
+                buf.align()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Replace a bare `EINVAL` from direct I/O with a more descriptive error.
+pub(crate) fn explain_einval(err: io::Error) -> io::Error {
+    #[cfg(not(windows))]
+    if err.raw_os_error() == Some(rustix::io::Errno::INVAL.raw_os_error()) {
+        return io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "direct I/O failed; the buffer or offset may not meet the file's alignment requirements",
+        );
+    }
+    err
+}
+
+/// Return the alignment required for buffer addresses, lengths, and file
+/// offsets in direct I/O on `file`.
+///
+/// On Linux, this uses the direct I/O alignment reported by `statx` where
+/// the kernel and filesystem support it, or the logical block size for block
+/// devices. Otherwise, this uses the filesystem's preferred I/O size, which
+/// is typically a multiple of the logical block size, or 4096 where that
+/// isn't available.
+pub fn direct_io_alignment<Filelike: AsFilelike>(file: &Filelike) -> io::Result<usize> {
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    {
+        use rustix::fs::{statx, AtFlags, FileType, StatxFlags};
+
+        match statx(
+            file.as_filelike(),
+            "",
+            AtFlags::EMPTY_PATH,
+            StatxFlags::DIOALIGN | StatxFlags::TYPE,
+        ) {
+            Ok(stx) => {
+                if stx.stx_mask & StatxFlags::DIOALIGN.bits() != 0 {
+                    let align = stx.stx_dio_mem_align.max(stx.stx_dio_offset_align);
+                    if align != 0 {
+                        return Ok(align as usize);
+                    }
+                }
+                if FileType::from_raw_mode(stx.stx_mode.into()) == FileType::BlockDevice {
+                    return Ok(rustix::fs::ioctl_blksszget(file.as_filelike())? as usize);
+                }
+            }
+            Err(rustix::io::Errno::NOSYS) => (),
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    #[cfg(not(windows))]
+    {
+        let blksize = rustix::fs::fstat(file.as_filelike())?.st_blksize;
+        if let Ok(blksize) = usize::try_from(blksize) {
+            if blksize.is_power_of_two() {
+                return Ok(blksize);
+            }
+        }
+    }
+
+    #[cfg(windows)]
+    let _ = file;
+
+    Ok(DEFAULT_ALIGNMENT)
+}
@@ -0,0 +1,115 @@
+//! The `Extents` iterator, and related types.
+
+use crate::fs::FileIoExt;
+use std::io;
+
+/// Whether an [`Extent`] holds data or is a hole.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum ExtentKind {
+    /// The extent may contain data.
+    Data,
+
+    /// The extent is a hole, which reads as zeros and may have no storage
+    /// allocated for it.
+    Hole,
+}
+
+/// A contiguous range of a file, reported by [`Extents`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Extent {
+    offset: u64,
+    len: u64,
+    kind: ExtentKind,
+}
+
+impl Extent {
+    /// Return the offset of the start of the extent.
+    #[inline]
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Return the length of the extent, in bytes.
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Test whether the extent is empty. Extents reported by [`Extents`] are
+    /// never empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return whether the extent holds data or is a hole.
+    #[inline]
+    pub fn kind(&self) -> ExtentKind {
+        self.kind
+    }
+}
+
+/// An iterator over the data and hole extents of a file, in order, returned
+/// by [`FileIoExt::extents`].
+///
+/// This uses [`FileIoExt::seek_data`] and [`FileIoExt::seek_hole`], so it
+/// changes the current position of the file.
+pub struct Extents<'a, Filelike: ?Sized> {
+    file: &'a Filelike,
+    offset: u64,
+    end: u64,
+}
+
+impl<'a, Filelike: FileIoExt + ?Sized> Extents<'a, Filelike> {
+    pub(crate) fn new(file: &'a Filelike, end: u64) -> Self {
+        Self {
+            file,
+            offset: 0,
+            end,
+        }
+    }
+
+    fn next_extent(&mut self) -> io::Result<Extent> {
+        let (end, kind) = match self.file.seek_data(self.offset)? {
+            Some(data) if data > self.offset => (data, ExtentKind::Hole),
+            Some(_) => match self.file.seek_hole(self.offset)? {
+                Some(hole) => (hole, ExtentKind::Data),
+                None => (self.end, ExtentKind::Data),
+            },
+            None => (self.end, ExtentKind::Hole),
+        };
+        // If the file changed concurrently, the seeks may disagree; treat the
+        // rest of the file as a single extent rather than looping.
+        let end = if end > self.offset {
+            end.min(self.end)
+        } else {
+            self.end
+        };
+        Ok(Extent {
+            offset: self.offset,
+            len: end - self.offset,
+            kind,
+        })
+    }
+}
+
+impl<Filelike: FileIoExt + ?Sized> Iterator for Extents<'_, Filelike> {
+    type Item = io::Result<Extent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.end {
+            return None;
+        }
+        match self.next_extent() {
+            Ok(extent) => {
+                self.offset += extent.len;
+                Some(Ok(extent))
+            }
+            Err(err) => {
+                // Stop after an error, rather than retrying the same offset.
+                self.offset = self.end;
+                Some(Err(err))
+            }
+        }
+    }
+}
@@ -10,7 +10,7 @@ use {
     cap_fs_ext::{OpenOptions, Reopen},
     cap_std::fs::OpenOptionsExt,
     io_lifetimes::AsHandle,
-    windows_sys::Win32::Storage::FileSystem::FILE_FLAG_WRITE_THROUGH,
+    windows_sys::Win32::Storage::FileSystem::{FILE_FLAG_NO_BUFFERING, FILE_FLAG_WRITE_THROUGH},
     winx::file::{AccessMode, FileModeInformation},
 };
 
@@ -73,6 +73,12 @@ bitflags! {
         /// Write I/O operations on the file descriptor shall complete as
         /// defined by synchronized I/O *file* integrity completion.
         const SYNC = 0x10;
+
+        /// I/O operations bypass the page cache where possible. Buffers and
+        /// offsets must typically be aligned; see [`AlignedBuf`].
+        ///
+        /// [`AlignedBuf`]: crate::fs::AlignedBuf
+        const DIRECT = 0x20;
     }
 }
 
@@ -89,6 +95,14 @@ impl<T> GetSetFdFlags for T {
         #[cfg(not(target_os = "freebsd"))]
         fd_flags.set(FdFlags::DSYNC, flags.contains(OFlags::DSYNC));
         fd_flags.set(FdFlags::NONBLOCK, flags.contains(OFlags::NONBLOCK));
+        #[cfg(any(
+            target_os = "android",
+            target_os = "freebsd",
+            target_os = "fuchsia",
+            target_os = "linux",
+            target_os = "netbsd"
+        ))]
+        fd_flags.set(FdFlags::DIRECT, flags.contains(OFlags::DIRECT));
         #[cfg(any(
             target_os = "ios",
             target_os = "macos",
@@ -119,11 +133,28 @@ impl<T> GetSetFdFlags for T {
         let mut flags = OFlags::empty();
         flags.set(OFlags::APPEND, fd_flags.contains(FdFlags::APPEND));
         flags.set(OFlags::NONBLOCK, fd_flags.contains(FdFlags::NONBLOCK));
+        #[cfg(any(
+            target_os = "android",
+            target_os = "freebsd",
+            target_os = "fuchsia",
+            target_os = "linux",
+            target_os = "netbsd"
+        ))]
+        flags.set(OFlags::DIRECT, fd_flags.contains(FdFlags::DIRECT));
+        #[cfg(not(any(
+            target_os = "android",
+            target_os = "freebsd",
+            target_os = "fuchsia",
+            target_os = "linux",
+            target_os = "netbsd"
+        )))]
+        if fd_flags.contains(FdFlags::DIRECT) {
+            return Err(unsupported_direct());
+        }
 
         // Linux, FreeBSD, and others silently ignore these flags in `F_SETFL`.
         if fd_flags.intersects(FdFlags::DSYNC | FdFlags::SYNC | FdFlags::RSYNC) {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
+            return Err(io::Error::other(
                 "setting fd_flags SYNC, DSYNC, and RSYNC is not supported",
             ));
         }
@@ -138,10 +169,13 @@ impl<T> GetSetFdFlags for T {
     where
         Self: AsFilelike + Sized,
     {
-        Ok(fcntl_setfl(
-            &*self.as_filelike_view::<fs::File>(),
-            set_fd_flags.flags,
-        )?)
+        match fcntl_setfl(&*self.as_filelike_view::<fs::File>(), set_fd_flags.flags) {
+            // The filesystem doesn't support `O_DIRECT`.
+            Err(rustix::io::Errno::INVAL) if set_fd_flags.flags.contains(OFlags::DIRECT) => {
+                Err(unsupported_direct())
+            }
+            otherwise => Ok(otherwise?),
+        }
     }
 }
 
@@ -169,6 +203,10 @@ impl<T> GetSetFdFlags for T {
             fd_flags |= FdFlags::DSYNC;
         }
 
+        if mode.contains(FileModeInformation::FILE_NO_INTERMEDIATE_BUFFERING) {
+            fd_flags |= FdFlags::DIRECT;
+        }
+
         Ok(fd_flags)
     }
 
@@ -181,6 +219,9 @@ impl<T> GetSetFdFlags for T {
         if fd_flags.contains(FdFlags::SYNC) || fd_flags.contains(FdFlags::DSYNC) {
             flags |= FILE_FLAG_WRITE_THROUGH;
         }
+        if fd_flags.contains(FdFlags::DIRECT) {
+            flags |= FILE_FLAG_NO_BUFFERING;
+        }
 
         let file = self.as_filelike_view::<fs::File>();
         let access_mode = winx::file::query_access_information(file.as_handle())?;
@@ -206,6 +247,14 @@ impl<T> GetSetFdFlags for T {
     }
 }
 
+#[cfg(not(windows))]
+fn unsupported_direct() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "direct I/O is not supported on this file",
+    )
+}
+
 #[cfg(windows)]
 fn file_access_mode_from_fd_flags(fd_flags: FdFlags, read: bool, write: bool) -> AccessMode {
     let mut access_mode = AccessMode::READ_CONTROL;
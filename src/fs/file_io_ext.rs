@@ -1,6 +1,14 @@
 //! The `FileIoExt` trait, and related utilities and impls.
 
-use crate::io::IoExt;
+use crate::fs::direct::{check_offset, explain_einval};
+#[cfg(all(
+    any(target_os = "android", target_os = "linux"),
+    feature = "use_io_uring"
+))]
+use crate::fs::Batch;
+use crate::fs::{AlignedBuf, Extents};
+use crate::io::{append_to_string, read_to_end_limited_with, BorrowedBuf, BorrowedCursor, IoExt};
+use bitflags::bitflags;
 use io_lifetimes::AsFilelike;
 #[cfg(not(any(
     windows,
@@ -23,11 +31,11 @@ use rustix::fs::{fallocate, FallocateFlags};
 #[cfg(not(any(windows, target_os = "ios", target_os = "macos", target_os = "redox")))]
 use rustix::io::{preadv, pwritev};
 use std::io::{self, IoSlice, IoSliceMut, Seek, SeekFrom};
-use std::slice;
+use std::{mem, slice};
+#[cfg(not(windows))]
+use {crate::io::IOV_MAX, rustix::fs::tell, rustix::fs::FileExt, rustix::io::pread_uninit};
 #[cfg(windows)]
 use {cap_fs_ext::Reopen, std::fs, std::os::windows::fs::FileExt};
-#[cfg(not(windows))]
-use {rustix::fs::tell, rustix::fs::FileExt};
 
 /// Advice to pass to `FileIoExt::advise`.
 #[cfg(not(any(
@@ -80,6 +88,50 @@ pub enum Advice {
     NoReuse,
 }
 
+bitflags! {
+    /// Per-call flags for [`FileIoExt::read_vectored_at_with_flags`] and
+    /// [`FileIoExt::write_vectored_at_with_flags`].
+    #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+    pub struct RwFlags: u32 {
+        /// The write completes as defined by synchronized I/O *data*
+        /// integrity completion, like a write followed by
+        /// [`FileIoExt::sync_data`].
+        ///
+        /// Where this is emulated with [`FileIoExt::sync_data`], a type
+        /// which doesn't implement it writes the data and then fails with
+        /// `io::ErrorKind::Unsupported`.
+        const DSYNC = 0x01;
+
+        /// The write completes as defined by synchronized I/O *file*
+        /// integrity completion, like a write followed by
+        /// [`FileIoExt::sync_all`].
+        ///
+        /// As with `DSYNC`, where this is emulated with
+        /// [`FileIoExt::sync_all`], a type which doesn't implement it writes
+        /// the data and then fails with `io::ErrorKind::Unsupported`.
+        const SYNC = 0x02;
+
+        /// Poll for completion instead of waiting for an interrupt. This is
+        /// only a hint, and is ignored where it isn't supported.
+        const HIPRI = 0x04;
+
+        /// Fail with `io::ErrorKind::WouldBlock` instead of waiting for data
+        /// to be read from storage, or for locks.
+        const NOWAIT = 0x08;
+    }
+}
+
+/// How [`FileIoExt::sync_range`] writes out data.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum SyncRangeMode {
+    /// Start writing out modified data in the range, without waiting for
+    /// it to complete.
+    Start,
+
+    /// Write out modified data in the range, and wait for it to complete.
+    Wait,
+}
+
 /// Extension trait for `std::fs::File` and `cap_std::fs::File`.
 pub trait FileIoExt: IoExt {
     /// Announce the expected access pattern of the data at the given offset.
@@ -89,6 +141,67 @@ pub trait FileIoExt: IoExt {
     /// ensuring that there are no holes under the given range.
     fn allocate(&self, offset: u64, len: u64) -> io::Result<()>;
 
+    /// Allocate space in the file, like [`FileIoExt::allocate`], but without
+    /// changing the file size, so space may be reserved past the end.
+    ///
+    /// This is similar to `fallocate` with `FALLOC_FL_KEEP_SIZE`. On
+    /// platforms without it, this fails with `io::ErrorKind::Unsupported`.
+    fn allocate_keep_size(&self, offset: u64, len: u64) -> io::Result<()> {
+        let _ = (offset, len);
+        Err(unsupported_fallocate("allocating without extending"))
+    }
+
+    /// Deallocate the space in the given range, so that it reads as zeros,
+    /// without changing the file size.
+    ///
+    /// This is similar to `fallocate` with `FALLOC_FL_PUNCH_HOLE`. If the
+    /// platform or filesystem doesn't support it, this fails with
+    /// `io::ErrorKind::Unsupported`.
+    fn punch_hole(&self, offset: u64, len: u64) -> io::Result<()> {
+        let _ = (offset, len);
+        Err(unsupported_fallocate("punching holes"))
+    }
+
+    /// Make the given range read as zeros, keeping space allocated for it,
+    /// and increasing the file size as needed.
+    ///
+    /// This is similar to `fallocate` with `FALLOC_FL_ZERO_RANGE`. If the
+    /// platform or filesystem doesn't support it, this fails with
+    /// `io::ErrorKind::Unsupported`.
+    fn zero_range(&self, offset: u64, len: u64) -> io::Result<()> {
+        let _ = (offset, len);
+        Err(unsupported_fallocate("zeroing ranges"))
+    }
+
+    /// Remove the given range from the file, shifting the data after it
+    /// down and reducing the file size by `len`, without rewriting the data.
+    ///
+    /// The range must not reach the end of the file, and filesystems
+    /// typically require `offset` and `len` to be multiples of their block
+    /// size.
+    ///
+    /// This is similar to `fallocate` with `FALLOC_FL_COLLAPSE_RANGE`. If the
+    /// platform or filesystem doesn't support it, this fails with
+    /// `io::ErrorKind::Unsupported`.
+    fn collapse_range(&self, offset: u64, len: u64) -> io::Result<()> {
+        let _ = (offset, len);
+        Err(unsupported_fallocate("collapsing ranges"))
+    }
+
+    /// Insert a hole of `len` bytes at `offset`, shifting the data after it
+    /// up and increasing the file size by `len`, without rewriting the data.
+    ///
+    /// `offset` must be less than the file size, and filesystems typically
+    /// require `offset` and `len` to be multiples of their block size.
+    ///
+    /// This is similar to `fallocate` with `FALLOC_FL_INSERT_RANGE`. If the
+    /// platform or filesystem doesn't support it, this fails with
+    /// `io::ErrorKind::Unsupported`.
+    fn insert_range(&self, offset: u64, len: u64) -> io::Result<()> {
+        let _ = (offset, len);
+        Err(unsupported_fallocate("inserting ranges"))
+    }
+
     /// Reads a number of bytes starting from a given offset.
     ///
     /// This is similar to [`std::os::unix::fs::FileExt::read_at`], except it
@@ -109,7 +222,38 @@ pub trait FileIoExt: IoExt {
     /// [`std::os::unix::fs::FileExt::read_exact_at`]: https://doc.rust-lang.org/std/os/unix/fs/trait.FileExt.html#tymethod.read_exact_at
     fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> io::Result<()>;
 
+    /// Pull some bytes from the given offset into `cursor`, advancing it by
+    /// the number of bytes read.
+    ///
+    /// This is to [`FileIoExt::read_at`] what the unstable
+    /// [`std::io::Read::read_buf`] is to `read`. The cursor's memory may be
+    /// uninitialized, and implementations which can read into uninitialized
+    /// memory do so without initializing it first. The default
+    /// implementation initializes the cursor and calls `read_at`.
+    ///
+    /// [`std::io::Read::read_buf`]: https://doc.rust-lang.org/std/io/trait.Read.html#method.read_buf
+    fn read_buf_at(&self, mut cursor: BorrowedCursor<'_>, offset: u64) -> io::Result<()> {
+        let n = self.read_at(cursor.ensure_init().init_mut(), offset)?;
+        cursor.advance(n);
+        Ok(())
+    }
+
+    /// Like [`FileIoExt::read_at`], but for direct I/O, checking that
+    /// `offset` is a multiple of `buf`'s alignment first.
+    ///
+    /// Misaligned offsets, and `EINVAL` errors from the kernel, are reported
+    /// as `io::ErrorKind::InvalidInput` with a description of the problem.
+    /// Near the end of the file, this may read fewer bytes than the
+    /// alignment.
+    fn read_at_aligned(&self, buf: &mut AlignedBuf, offset: u64) -> io::Result<usize> {
+        check_offset(buf, offset)?;
+        self.read_at(buf, offset).map_err(explain_einval)
+    }
+
     /// Is to `read_vectored` what `read_at` is to `read`.
+    ///
+    /// Like `read_vectored`, this may read into fewer buffers than it's
+    /// given, such as when there are more than the platform's `IOV_MAX`.
     fn read_vectored_at(&self, bufs: &mut [IoSliceMut], offset: u64) -> io::Result<usize> {
         // By default, just read into the first non-empty slice.
         let buf = bufs
@@ -137,7 +281,7 @@ pub trait FileIoExt: IoExt {
                 Ok(nread) => {
                     offset = offset
                         .checked_add(nread.try_into().unwrap())
-                        .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "offset overflow"))?;
+                        .ok_or_else(|| io::Error::other("offset overflow"))?;
                     bufs = advance_mut(bufs, nread);
                 }
                 Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
@@ -148,6 +292,22 @@ pub trait FileIoExt: IoExt {
         Ok(())
     }
 
+    /// Like [`FileIoExt::read_vectored_at`], but with per-call `flags`.
+    ///
+    /// On Linux, this uses [`preadv2`]. Elsewhere, or on kernels without
+    /// it, `DSYNC`, `SYNC`, and `HIPRI` are ignored, since they don't affect
+    /// reads, and `NOWAIT` fails with `io::ErrorKind::Unsupported`.
+    ///
+    /// [`preadv2`]: https://man7.org/linux/man-pages/man2/preadv2.2.html
+    fn read_vectored_at_with_flags(
+        &self,
+        bufs: &mut [IoSliceMut],
+        offset: u64,
+        flags: RwFlags,
+    ) -> io::Result<usize> {
+        read_vectored_at_with_emulated_flags(self, bufs, offset, flags)
+    }
+
     /// Determines if this `FileIoExt` implementation has an efficient
     /// `read_vectored_at` implementation.
     #[inline]
@@ -155,14 +315,72 @@ pub trait FileIoExt: IoExt {
         false
     }
 
+    /// Reads into several buffers, each from its own offset, and returns a
+    /// result for each `(offset, buf)` request, in the same order.
+    ///
+    /// Each result is the number of bytes read into the request's buffer,
+    /// which may be less than its length, as with [`FileIoExt::read_at`].
+    /// Requests may be performed in any order, and concurrently.
+    ///
+    /// On Linux and Android with the `use_io_uring` feature, the reads are
+    /// submitted together through an io_uring, which each thread creates the
+    /// first time and keeps for reuse. Otherwise, or if io_uring is
+    /// unavailable, they're performed in order of offset, with requests for
+    /// adjacent ranges coalesced into single [`FileIoExt::read_vectored_at`]
+    /// calls.
+    fn read_many_at(&self, reqs: &mut [(u64, &mut [u8])]) -> Vec<io::Result<usize>> {
+        read_many_at_by_coalescing(self, reqs)
+    }
+
     /// Read all bytes, starting at `offset`, until EOF in this source, placing
     /// them into `buf`.
+    ///
+    /// The file's size is only used as a hint for how much to read, so this
+    /// works with files which report a size of zero, such as those in procfs
+    /// and sysfs, and with files which grow while being read.
     fn read_to_end_at(&self, buf: &mut Vec<u8>, offset: u64) -> io::Result<usize>;
 
     /// Read all bytes, starting at `offset`, until EOF in this source,
     /// appending them to `buf`.
+    ///
+    /// Like [`FileIoExt::read_to_end_at`], this reads until EOF rather than
+    /// relying on the file's size.
     fn read_to_string_at(&self, buf: &mut String, offset: u64) -> io::Result<usize>;
 
+    /// Read all bytes, starting at `offset`, until EOF in this source, placing
+    /// them into `buf`, unless there are more than `max` of them.
+    ///
+    /// If the source has more than `max` bytes after `offset`, this fails
+    /// with [`io::ErrorKind::FileTooLarge`], leaving the first `max` bytes in
+    /// `buf`. Unlike [`IoExt::read_to_end_limited`], this doesn't consume
+    /// anything, so the byte it reads past the limit to tell isn't kept.
+    fn read_to_end_at_limited(
+        &self,
+        buf: &mut Vec<u8>,
+        offset: u64,
+        max: usize,
+    ) -> io::Result<usize> {
+        read_to_end_limited_with(buf, max, false, |cursor, pos| {
+            self.read_buf_at(cursor, offset.saturating_add(pos))
+        })
+    }
+
+    /// Read all bytes, starting at `offset`, until EOF in this source,
+    /// appending them to `buf`, unless there are more than `max` of them.
+    ///
+    /// This is to [`FileIoExt::read_to_end_at_limited`] what
+    /// [`FileIoExt::read_to_string_at`] is to [`FileIoExt::read_to_end_at`].
+    /// If the limit is hit, the bytes before it are left in `buf`, except for
+    /// the start of a UTF-8 sequence that the limit cuts short.
+    fn read_to_string_at_limited(
+        &self,
+        buf: &mut String,
+        offset: u64,
+        max: usize,
+    ) -> io::Result<usize> {
+        append_to_string(buf, |vec| self.read_to_end_at_limited(vec, offset, max))
+    }
+
     /// Writes a number of bytes starting from a given offset.
     ///
     /// This is similar to [`std::os::unix::fs::FileExt::write_at`], except it
@@ -179,6 +397,16 @@ pub trait FileIoExt: IoExt {
     /// [`std::os::unix::fs::FileExt::write_at`]: https://doc.rust-lang.org/std/os/unix/fs/trait.FileExt.html#tymethod.write_at
     fn write_at(&self, buf: &[u8], offset: u64) -> io::Result<usize>;
 
+    /// Like [`FileIoExt::write_at`], but for direct I/O, checking that
+    /// `offset` is a multiple of `buf`'s alignment first.
+    ///
+    /// Misaligned offsets, and `EINVAL` errors from the kernel, are reported
+    /// as `io::ErrorKind::InvalidInput` with a description of the problem.
+    fn write_at_aligned(&self, buf: &AlignedBuf, offset: u64) -> io::Result<usize> {
+        check_offset(buf, offset)?;
+        self.write_at(buf, offset).map_err(explain_einval)
+    }
+
     /// Attempts to write an entire buffer starting from a given offset.
     ///
     /// This is similar to [`std::os::unix::fs::FileExt::write_all_at`], except
@@ -208,6 +436,9 @@ pub trait FileIoExt: IoExt {
     }
 
     /// Is to `write_vectored` what `write_at` is to `write`.
+    ///
+    /// Like `write_vectored`, this may write from fewer buffers than it's
+    /// given, such as when there are more than the platform's `IOV_MAX`.
     fn write_vectored_at(&self, bufs: &[IoSlice], offset: u64) -> io::Result<usize> {
         // By default, just write the first non-empty slice.
         let buf = bufs
@@ -224,7 +455,7 @@ pub trait FileIoExt: IoExt {
                 Ok(nwritten) => {
                     offset = offset
                         .checked_add(nwritten.try_into().unwrap())
-                        .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "offset overflow"))?;
+                        .ok_or_else(|| io::Error::other("offset overflow"))?;
                     bufs = advance(bufs, nwritten);
                 }
                 Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
@@ -234,6 +465,24 @@ pub trait FileIoExt: IoExt {
         Ok(())
     }
 
+    /// Like [`FileIoExt::write_vectored_at`], but with per-call `flags`.
+    ///
+    /// On Linux, this uses [`pwritev2`]. Elsewhere, or on kernels without
+    /// it, `DSYNC` and `SYNC` are emulated by calling
+    /// [`FileIoExt::sync_data`] or [`FileIoExt::sync_all`] after the write,
+    /// `HIPRI` is ignored, and `NOWAIT` fails with
+    /// `io::ErrorKind::Unsupported`.
+    ///
+    /// [`pwritev2`]: https://man7.org/linux/man-pages/man2/pwritev2.2.html
+    fn write_vectored_at_with_flags(
+        &self,
+        bufs: &[IoSlice],
+        offset: u64,
+        flags: RwFlags,
+    ) -> io::Result<usize> {
+        write_vectored_at_with_emulated_flags(self, bufs, offset, flags)
+    }
+
     /// Determines if this `FileIoExt` implementation has an efficient
     /// `write_vectored_at` implementation.
     #[inline]
@@ -241,6 +490,20 @@ pub trait FileIoExt: IoExt {
         false
     }
 
+    /// Writes several buffers, each at its own offset, and returns a result
+    /// for each `(offset, buf)` request, in the same order.
+    ///
+    /// Each result is the number of bytes written from the request's buffer,
+    /// which may be less than its length, as with [`FileIoExt::write_at`].
+    /// Requests may be performed in any order, and concurrently, so the
+    /// contents of ranges written by more than one request are unspecified.
+    ///
+    /// This is batched and coalesced in the same way as
+    /// [`FileIoExt::read_many_at`].
+    fn write_many_at(&self, reqs: &[(u64, &[u8])]) -> Vec<io::Result<usize>> {
+        write_many_at_by_coalescing(self, reqs)
+    }
+
     /// Writes a number of bytes at the end of a file.
     ///
     /// This leaves the current position of the file unmodified.
@@ -275,6 +538,10 @@ pub trait FileIoExt: IoExt {
     }
 
     /// Is to `append` what `write_vectored` is to `write`.
+    ///
+    /// On Unix-family platforms, the buffers are appended in a single write,
+    /// so this fails with [`io::ErrorKind::InvalidInput`] if there are more
+    /// of them than the platform's `IOV_MAX`, rather than splitting them.
     fn append_vectored(&self, bufs: &[IoSlice]) -> io::Result<usize> {
         // By default, just append the first non-empty slice.
         let buf = bufs
@@ -305,6 +572,47 @@ pub trait FileIoExt: IoExt {
         false
     }
 
+    /// Copy up to `len` bytes from this file, starting at `src_offset`, to
+    /// `dst`, starting at `dst_offset`, and return the number of bytes
+    /// copied.
+    ///
+    /// Fewer than `len` bytes are copied only if the end of this file is
+    /// reached. Neither file's current position is used or changed.
+    ///
+    /// On Linux, this uses [`copy_file_range`], so the copy happens in the
+    /// kernel, and filesystems which support it may share the data with a
+    /// reflink instead of copying it. Elsewhere, or if the kernel refuses,
+    /// for example because the files are on different filesystems, this
+    /// falls back to copying with [`FileIoExt::read_at`] and
+    /// [`FileIoExt::write_all_at`].
+    ///
+    /// [`copy_file_range`]: https://man7.org/linux/man-pages/man2/copy_file_range.2.html
+    fn copy_range_to<Dst: AsFilelike + FileIoExt>(
+        &self,
+        dst: &Dst,
+        src_offset: u64,
+        dst_offset: u64,
+        len: u64,
+    ) -> io::Result<u64>
+    where
+        Self: Sized,
+    {
+        copy_range_by_copying(self, dst, src_offset, dst_offset, len)
+    }
+
+    /// Write up to `len` bytes from this file, starting at `offset`, to
+    /// `dst`, such as a socket or a pipe, and return the number of bytes
+    /// written.
+    ///
+    /// This is the same as [`IoExt::transfer_from`] with the arguments
+    /// swapped; see there for details.
+    fn transfer_to<Dst: IoExt>(&self, dst: &Dst, offset: u64, len: u64) -> io::Result<u64>
+    where
+        Self: AsFilelike + Sized,
+    {
+        dst.transfer_from(self, offset, len)
+    }
+
     /// Seek to an offset, in bytes, in a stream.
     ///
     /// This is similar to [`std::io::Seek::seek`], except it takes `self` by
@@ -323,6 +631,460 @@ pub trait FileIoExt: IoExt {
     /// [`std::io::Seek::stream_position`]: https://doc.rust-lang.org/std/io/trait.Seek.html#method.stream_position
     /// [rust-lang/rust#62726]: https://github.com/rust-lang/rust/issues/59359.
     fn stream_position(&self) -> io::Result<u64>;
+
+    /// Flush modified data to storage, without necessarily flushing
+    /// metadata which isn't needed to read it back.
+    ///
+    /// This is the same as [`std::fs::File::sync_data`], except that it's
+    /// available for all types which implement `FileIoExt`. The default
+    /// implementation fails with [`io::ErrorKind::Unsupported`], and so do
+    /// the default [`FileIoExt::write_vectored_at_with_flags`] with
+    /// [`RwFlags::DSYNC`] and [`FileIoExt::sync_range`], though the former
+    /// has already written the data by then.
+    fn sync_data(&self) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// Flush modified data and metadata to storage.
+    ///
+    /// This is the same as [`std::fs::File::sync_all`], except that it's
+    /// available for all types which implement `FileIoExt`. The default
+    /// implementation fails with [`io::ErrorKind::Unsupported`], and so does
+    /// the default [`FileIoExt::write_vectored_at_with_flags`] with
+    /// [`RwFlags::SYNC`], after writing the data.
+    fn sync_all(&self) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// Write out modified data in the range starting at `offset` and
+    /// extending for `len` bytes, or to the end of the file if `len` is
+    /// zero.
+    ///
+    /// On Linux and Android, this uses [`sync_file_range`], which only
+    /// writes out the given range. It doesn't flush metadata or the storage
+    /// device's write cache, so it doesn't guarantee that the data survives
+    /// a crash; use [`FileIoExt::sync_data`] for that, for example after
+    /// `SyncRangeMode::Start` has started writing out several ranges.
+    /// Elsewhere, this uses [`FileIoExt::sync_data`] on the whole file.
+    ///
+    /// [`sync_file_range`]: https://man7.org/linux/man-pages/man2/sync_file_range.2.html
+    fn sync_range(&self, offset: u64, len: u64, mode: SyncRangeMode) -> io::Result<()> {
+        let _ = (offset, len, mode);
+        self.sync_data()
+    }
+
+    /// Seek to the start of the next region of the file containing data, at
+    /// or after `offset`, and return its offset.
+    ///
+    /// This returns `Ok(None)`, and leaves the position unchanged, if
+    /// `offset` is at or past the end of the file, or if there's no data
+    /// after it.
+    ///
+    /// This is similar to [`lseek`] with `SEEK_DATA`. On filesystems and
+    /// platforms which don't track holes, the whole file is considered to be
+    /// data.
+    ///
+    /// [`lseek`]: https://man7.org/linux/man-pages/man2/lseek.2.html
+    fn seek_data(&self, offset: u64) -> io::Result<Option<u64>> {
+        seek_data_whole_file(self, offset)
+    }
+
+    /// Seek to the start of the next hole in the file, at or after `offset`,
+    /// and return its offset.
+    ///
+    /// Every file has an implicit hole at its end, so this returns the size
+    /// of the file if there are no holes after `offset`. This returns
+    /// `Ok(None)`, and leaves the position unchanged, if `offset` is at or
+    /// past the end of the file.
+    ///
+    /// This is similar to [`lseek`] with `SEEK_HOLE`. On filesystems and
+    /// platforms which don't track holes, the whole file is considered to be
+    /// data.
+    ///
+    /// [`lseek`]: https://man7.org/linux/man-pages/man2/lseek.2.html
+    fn seek_hole(&self, offset: u64) -> io::Result<Option<u64>> {
+        seek_hole_whole_file(self, offset)
+    }
+
+    /// Return an iterator over the data and hole extents of the file, from
+    /// the start to the current end of the file.
+    ///
+    /// This uses [`FileIoExt::seek_data`] and [`FileIoExt::seek_hole`], so
+    /// it changes the current position.
+    fn extents(&self) -> io::Result<Extents<'_, Self>>
+    where
+        Self: Sized,
+    {
+        let end = self.seek(SeekFrom::End(0))?;
+        Ok(Extents::new(self, end))
+    }
+}
+
+/// Implement `read_many_at` with a `read_vectored_at` call for each run of
+/// requests for adjacent ranges.
+fn read_many_at_by_coalescing<Filelike: FileIoExt + ?Sized>(
+    file: &Filelike,
+    reqs: &mut [(u64, &mut [u8])],
+) -> Vec<io::Result<usize>> {
+    let mut results: Vec<io::Result<usize>> = reqs.iter().map(|_| Ok(0)).collect();
+
+    // Sort the requests by offset, leaving out empty ones, which read nothing.
+    let mut sorted: Vec<(usize, &mut (u64, &mut [u8]))> = reqs
+        .iter_mut()
+        .enumerate()
+        .filter(|(_, (_, buf))| !buf.is_empty())
+        .collect();
+    sorted.sort_by_key(|(_, (offset, _))| *offset);
+
+    let mut rest = &mut sorted[..];
+    while !rest.is_empty() {
+        let len = coalesced_len(rest.iter().map(|(_, (offset, buf))| (*offset, buf.len())));
+        let (run, tail) = mem::take(&mut rest).split_at_mut(len);
+        rest = tail;
+
+        let offset = run[0].1 .0;
+        let mut bufs: Vec<IoSliceMut> = run
+            .iter_mut()
+            .map(|(_, (_, buf))| IoSliceMut::new(buf))
+            .collect();
+        match read_vectored_at_until_eof(file, &mut bufs, offset) {
+            Ok(mut nread) => {
+                for (index, (_, buf)) in run.iter() {
+                    let n = nread.min(buf.len());
+                    results[*index] = Ok(n);
+                    nread -= n;
+                }
+            }
+            // Redo the requests one at a time, so that each gets its own
+            // result.
+            Err(_) => {
+                drop(bufs);
+                for (index, (offset, buf)) in run.iter_mut() {
+                    results[*index] = file.read_at(buf, *offset);
+                }
+            }
+        }
+    }
+
+    results
+}
+
+/// Implement `write_many_at` with a `write_vectored_at` call for each run of
+/// requests for adjacent ranges.
+fn write_many_at_by_coalescing<Filelike: FileIoExt + ?Sized>(
+    file: &Filelike,
+    reqs: &[(u64, &[u8])],
+) -> Vec<io::Result<usize>> {
+    let mut results: Vec<io::Result<usize>> = reqs.iter().map(|_| Ok(0)).collect();
+
+    // Sort the requests by offset, leaving out empty ones, which write
+    // nothing. The sort is stable, so requests for the same offset are
+    // performed in order.
+    let mut sorted: Vec<(usize, &(u64, &[u8]))> = reqs
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, buf))| !buf.is_empty())
+        .collect();
+    sorted.sort_by_key(|(_, (offset, _))| *offset);
+
+    let mut rest = &sorted[..];
+    while !rest.is_empty() {
+        let len = coalesced_len(rest.iter().map(|(_, (offset, buf))| (*offset, buf.len())));
+        let (run, tail) = rest.split_at(len);
+        rest = tail;
+
+        let offset = run[0].1 .0;
+        let mut bufs: Vec<IoSlice> = run.iter().map(|(_, (_, buf))| IoSlice::new(buf)).collect();
+        match write_vectored_at_until_zero(file, &mut bufs, offset) {
+            Ok(mut nwritten) => {
+                for (index, (_, buf)) in run {
+                    let n = nwritten.min(buf.len());
+                    results[*index] = Ok(n);
+                    nwritten -= n;
+                }
+            }
+            // Redo the requests one at a time, so that each gets its own
+            // result.
+            Err(_) => {
+                for (index, (offset, buf)) in run {
+                    results[*index] = file.write_at(buf, *offset);
+                }
+            }
+        }
+    }
+
+    results
+}
+
+/// Return the number of leading `(offset, len)` requests which cover
+/// adjacent ranges.
+fn coalesced_len(mut reqs: impl Iterator<Item = (u64, usize)>) -> usize {
+    let mut end = match reqs.next() {
+        Some((offset, len)) => offset.checked_add(len as u64),
+        None => return 0,
+    };
+    let mut count = 1;
+    for (offset, len) in reqs {
+        if end != Some(offset) {
+            break;
+        }
+        end = offset.checked_add(len as u64);
+        count += 1;
+    }
+    count
+}
+
+/// Like `read_exact_vectored_at`, but stop at EOF rather than failing, and
+/// return the number of bytes read.
+fn read_vectored_at_until_eof<Filelike: FileIoExt + ?Sized>(
+    file: &Filelike,
+    mut bufs: &mut [IoSliceMut],
+    mut offset: u64,
+) -> io::Result<usize> {
+    let mut total = 0;
+    while !bufs.is_empty() {
+        match file.read_vectored_at(bufs, offset) {
+            Ok(0) => break,
+            Ok(nread) => {
+                total += nread;
+                offset += nread as u64;
+                bufs = advance_mut(bufs, nread);
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(total)
+}
+
+/// Like `write_all_vectored_at`, but stop if a write writes nothing rather
+/// than failing, and return the number of bytes written.
+fn write_vectored_at_until_zero<Filelike: FileIoExt + ?Sized>(
+    file: &Filelike,
+    mut bufs: &mut [IoSlice],
+    mut offset: u64,
+) -> io::Result<usize> {
+    let mut total = 0;
+    while !bufs.is_empty() {
+        match file.write_vectored_at(bufs, offset) {
+            Ok(0) => break,
+            Ok(nwritten) => {
+                total += nwritten;
+                offset += nwritten as u64;
+                bufs = advance(bufs, nwritten);
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(total)
+}
+
+/// Submit `read_many_at` requests through this thread's cached io_uring, if
+/// io_uring is available.
+///
+/// Returns `None` if io_uring is unavailable, or if the submission fails.
+/// `Batch::submit` only fails when none of the operations are in flight, so
+/// the caller is free to reuse the buffers in `reqs` after that.
+#[cfg(all(
+    any(target_os = "android", target_os = "linux"),
+    feature = "use_io_uring"
+))]
+fn read_many_at_with_io_uring<Filelike: AsFilelike + FileIoExt>(
+    file: &Filelike,
+    reqs: &mut [(u64, &mut [u8])],
+) -> Option<Vec<io::Result<usize>>> {
+    Batch::with_cached_ring(move |batch| {
+        for (offset, buf) in reqs {
+            batch.read_at(file, buf, *offset);
+        }
+        batch.submit().ok()
+    })?
+}
+
+/// Submit `write_many_at` requests through this thread's cached io_uring, if
+/// io_uring is available.
+///
+/// As with `read_many_at_with_io_uring`, `None` means that none of the
+/// operations are in flight.
+#[cfg(all(
+    any(target_os = "android", target_os = "linux"),
+    feature = "use_io_uring"
+))]
+fn write_many_at_with_io_uring<Filelike: AsFilelike + FileIoExt>(
+    file: &Filelike,
+    reqs: &[(u64, &[u8])],
+) -> Option<Vec<io::Result<usize>>> {
+    let bufs: Vec<IoSlice> = reqs.iter().map(|(_, buf)| IoSlice::new(buf)).collect();
+    Batch::with_cached_ring(|batch| {
+        for ((offset, _), buf) in reqs.iter().zip(&bufs) {
+            batch.write_vectored_at(file, slice::from_ref(buf), *offset);
+        }
+        batch.submit().ok()
+    })?
+}
+
+/// Limit `bufs` to the number of buffers the vectored I/O system calls
+/// accept. Callers handle short reads and writes already, so the rest are
+/// picked up on the next call.
+///
+/// Leading empty buffers are skipped first, so that a long run of them
+/// doesn't make the call transfer nothing and look like EOF.
+#[cfg(not(any(windows, target_os = "ios", target_os = "macos", target_os = "redox")))]
+fn iov_max<'a, 'b>(bufs: &'a [IoSlice<'b>]) -> &'a [IoSlice<'b>] {
+    let start = bufs.iter().take_while(|buf| buf.is_empty()).count();
+    let bufs = &bufs[start..];
+    &bufs[..bufs.len().min(IOV_MAX)]
+}
+
+/// Like `iov_max`, but for `IoSliceMut`s.
+#[cfg(not(any(windows, target_os = "ios", target_os = "macos", target_os = "redox")))]
+fn iov_max_mut<'a, 'b>(bufs: &'a mut [IoSliceMut<'b>]) -> &'a mut [IoSliceMut<'b>] {
+    let start = bufs.iter().take_while(|buf| buf.is_empty()).count();
+    let bufs = &mut bufs[start..];
+    let len = bufs.len().min(IOV_MAX);
+    &mut bufs[..len]
+}
+
+/// Implement `read_vectored_at_with_flags` without `preadv2`.
+fn read_vectored_at_with_emulated_flags<Filelike: FileIoExt + ?Sized>(
+    file: &Filelike,
+    bufs: &mut [IoSliceMut],
+    offset: u64,
+    flags: RwFlags,
+) -> io::Result<usize> {
+    if flags.contains(RwFlags::NOWAIT) {
+        return Err(unsupported_nowait());
+    }
+    file.read_vectored_at(bufs, offset)
+}
+
+/// Implement `write_vectored_at_with_flags` without `pwritev2`.
+fn write_vectored_at_with_emulated_flags<Filelike: FileIoExt + ?Sized>(
+    file: &Filelike,
+    bufs: &[IoSlice],
+    offset: u64,
+    flags: RwFlags,
+) -> io::Result<usize> {
+    if flags.contains(RwFlags::NOWAIT) {
+        return Err(unsupported_nowait());
+    }
+    let nwritten = file.write_vectored_at(bufs, offset)?;
+    if flags.contains(RwFlags::SYNC) {
+        file.sync_all()?;
+    } else if flags.contains(RwFlags::DSYNC) {
+        file.sync_data()?;
+    }
+    Ok(nwritten)
+}
+
+fn unsupported_nowait() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "non-blocking positional I/O is not supported on this file",
+    )
+}
+
+/// Convert `RwFlags` to the flags used by `preadv2` and `pwritev2`.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn to_rustix_rw_flags(flags: RwFlags) -> rustix::io::ReadWriteFlags {
+    use rustix::io::ReadWriteFlags;
+
+    let mut result = ReadWriteFlags::empty();
+    result.set(ReadWriteFlags::DSYNC, flags.contains(RwFlags::DSYNC));
+    result.set(ReadWriteFlags::SYNC, flags.contains(RwFlags::SYNC));
+    result.set(ReadWriteFlags::HIPRI, flags.contains(RwFlags::HIPRI));
+    result.set(ReadWriteFlags::NOWAIT, flags.contains(RwFlags::NOWAIT));
+    result
+}
+
+/// Implement `copy_range_to` by reading into a buffer and writing it out.
+fn copy_range_by_copying<Src: FileIoExt + ?Sized, Dst: FileIoExt + ?Sized>(
+    src: &Src,
+    dst: &Dst,
+    src_offset: u64,
+    dst_offset: u64,
+    len: u64,
+) -> io::Result<u64> {
+    let mut buf = vec![0; len.min(COPY_BUF_SIZE) as usize];
+    let mut copied = 0;
+    while copied < len {
+        let chunk = (len - copied).min(buf.len() as u64) as usize;
+        let src_pos = src_offset
+            .checked_add(copied)
+            .ok_or_else(|| io::Error::other("offset overflow"))?;
+        let dst_pos = dst_offset
+            .checked_add(copied)
+            .ok_or_else(|| io::Error::other("offset overflow"))?;
+        let n = match src.read_at(&mut buf[..chunk], src_pos) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        dst.write_all_at(&buf[..n], dst_pos)?;
+        copied += n as u64;
+    }
+    Ok(copied)
+}
+
+/// The size of the buffer used by `copy_range_by_copying`, and by
+/// `IoExt::transfer_from` when it can't avoid copying.
+pub(crate) const COPY_BUF_SIZE: u64 = 64 * 1024;
+
+fn unsupported_fallocate(what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{} is not supported on this file", what),
+    )
+}
+
+/// Call `fallocate` with `flags`, reporting unsupported modes as
+/// `io::ErrorKind::Unsupported`.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn fallocate_with<Filelike: AsFilelike>(
+    file: &Filelike,
+    flags: FallocateFlags,
+    offset: u64,
+    len: u64,
+    what: &str,
+) -> io::Result<()> {
+    match fallocate(file, flags, offset, len) {
+        Err(rustix::io::Errno::NOTSUP) | Err(rustix::io::Errno::NOSYS) => {
+            Err(unsupported_fallocate(what))
+        }
+        otherwise => Ok(otherwise?),
+    }
+}
+
+/// Implement `seek_data` for files which are entirely data.
+fn seek_data_whole_file<Filelike: FileIoExt + ?Sized>(
+    file: &Filelike,
+    offset: u64,
+) -> io::Result<Option<u64>> {
+    let pos = file.stream_position()?;
+    let len = file.seek(SeekFrom::End(0))?;
+    if offset < len {
+        Ok(Some(file.seek(SeekFrom::Start(offset))?))
+    } else {
+        file.seek(SeekFrom::Start(pos))?;
+        Ok(None)
+    }
+}
+
+/// Implement `seek_hole` for files which are entirely data.
+fn seek_hole_whole_file<Filelike: FileIoExt + ?Sized>(
+    file: &Filelike,
+    offset: u64,
+) -> io::Result<Option<u64>> {
+    let pos = file.stream_position()?;
+    let len = file.seek(SeekFrom::End(0))?;
+    if offset < len {
+        Ok(Some(len))
+    } else {
+        file.seek(SeekFrom::Start(pos))?;
+        Ok(None)
+    }
 }
 
 /// Skip any leading elements in `bufs` which are empty buffers.
@@ -455,6 +1217,67 @@ impl<T: AsFilelike + IoExt> FileIoExt for T {
         Ok(fallocate(self, FallocateFlags::empty(), offset, len)?)
     }
 
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    #[inline]
+    fn allocate_keep_size(&self, offset: u64, len: u64) -> io::Result<()> {
+        fallocate_with(
+            self,
+            FallocateFlags::KEEP_SIZE,
+            offset,
+            len,
+            "allocating without extending",
+        )
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    #[inline]
+    fn punch_hole(&self, offset: u64, len: u64) -> io::Result<()> {
+        // The kernel requires `PUNCH_HOLE` to be combined with `KEEP_SIZE`.
+        fallocate_with(
+            self,
+            FallocateFlags::PUNCH_HOLE | FallocateFlags::KEEP_SIZE,
+            offset,
+            len,
+            "punching holes",
+        )
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    #[inline]
+    fn zero_range(&self, offset: u64, len: u64) -> io::Result<()> {
+        fallocate_with(
+            self,
+            FallocateFlags::ZERO_RANGE,
+            offset,
+            len,
+            "zeroing ranges",
+        )
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    #[inline]
+    fn collapse_range(&self, offset: u64, len: u64) -> io::Result<()> {
+        fallocate_with(
+            self,
+            FallocateFlags::COLLAPSE_RANGE,
+            offset,
+            len,
+            "collapsing ranges",
+        )
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    #[inline]
+    fn insert_range(&self, offset: u64, len: u64) -> io::Result<()> {
+        fallocate_with(
+            self,
+            FallocateFlags::INSERT_RANGE,
+            offset,
+            len,
+            "inserting ranges",
+        )
+    }
+
     #[cfg(target_os = "netbsd")]
     fn allocate(&self, _offset: u64, _len: u64) -> io::Result<()> {
         todo!("NetBSD 7.0 supports posix_fallocate; add bindings for it")
@@ -475,6 +1298,16 @@ impl<T: AsFilelike + IoExt> FileIoExt for T {
         FileExt::read_at(&*self.as_filelike_view::<std::fs::File>(), buf, offset)
     }
 
+    #[inline]
+    fn read_buf_at(&self, mut cursor: BorrowedCursor<'_>, offset: u64) -> io::Result<()> {
+        // SAFETY: `pread_uninit` doesn't de-initialize any bytes.
+        let (init, _) = pread_uninit(self, unsafe { cursor.as_mut() }, offset)?;
+        let n = init.len();
+        // SAFETY: `pread_uninit` initialized the first `n` bytes.
+        unsafe { cursor.advance_unchecked(n) };
+        Ok(())
+    }
+
     #[inline]
     fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> io::Result<()> {
         FileExt::read_exact_at(&*self.as_filelike_view::<std::fs::File>(), buf, offset)
@@ -483,7 +1316,7 @@ impl<T: AsFilelike + IoExt> FileIoExt for T {
     #[cfg(not(any(target_os = "ios", target_os = "macos", target_os = "redox")))]
     #[inline]
     fn read_vectored_at(&self, bufs: &mut [IoSliceMut], offset: u64) -> io::Result<usize> {
-        Ok(preadv(self, bufs, offset)?)
+        Ok(preadv(self, iov_max_mut(bufs), offset)?)
     }
 
     #[cfg(not(any(target_os = "ios", target_os = "macos", target_os = "redox")))]
@@ -492,6 +1325,24 @@ impl<T: AsFilelike + IoExt> FileIoExt for T {
         true
     }
 
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    fn read_vectored_at_with_flags(
+        &self,
+        bufs: &mut [IoSliceMut],
+        offset: u64,
+        flags: RwFlags,
+    ) -> io::Result<usize> {
+        use rustix::io::{preadv2, Errno};
+
+        match preadv2(self, iov_max_mut(bufs), offset, to_rustix_rw_flags(flags)) {
+            // `NOTSUP` means the file doesn't support one of the flags.
+            Err(Errno::NOSYS) | Err(Errno::NOTSUP) => {
+                read_vectored_at_with_emulated_flags(self, bufs, offset, flags)
+            }
+            otherwise => Ok(otherwise?),
+        }
+    }
+
     #[inline]
     fn read_to_end_at(&self, buf: &mut Vec<u8>, offset: u64) -> io::Result<usize> {
         read_to_end_at(&self.as_filelike_view::<std::fs::File>(), buf, offset)
@@ -515,7 +1366,7 @@ impl<T: AsFilelike + IoExt> FileIoExt for T {
     #[cfg(not(any(target_os = "ios", target_os = "macos", target_os = "redox")))]
     #[inline]
     fn write_vectored_at(&self, bufs: &[IoSlice], offset: u64) -> io::Result<usize> {
-        Ok(pwritev(self, bufs, offset)?)
+        Ok(pwritev(self, iov_max(bufs), offset)?)
     }
 
     #[cfg(not(any(target_os = "ios", target_os = "macos", target_os = "redox")))]
@@ -524,6 +1375,50 @@ impl<T: AsFilelike + IoExt> FileIoExt for T {
         true
     }
 
+    #[cfg(all(
+        any(target_os = "android", target_os = "linux"),
+        feature = "use_io_uring"
+    ))]
+    fn read_many_at(&self, reqs: &mut [(u64, &mut [u8])]) -> Vec<io::Result<usize>> {
+        // Retrying after a failed submission is fine, since nothing is left
+        // in flight, and reads at fixed offsets can be repeated.
+        match read_many_at_with_io_uring(self, reqs) {
+            Some(results) => results,
+            None => read_many_at_by_coalescing(self, reqs),
+        }
+    }
+
+    #[cfg(all(
+        any(target_os = "android", target_os = "linux"),
+        feature = "use_io_uring"
+    ))]
+    fn write_many_at(&self, reqs: &[(u64, &[u8])]) -> Vec<io::Result<usize>> {
+        // As with reads, writes of the same data at fixed offsets can be
+        // repeated.
+        match write_many_at_with_io_uring(self, reqs) {
+            Some(results) => results,
+            None => write_many_at_by_coalescing(self, reqs),
+        }
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    fn write_vectored_at_with_flags(
+        &self,
+        bufs: &[IoSlice],
+        offset: u64,
+        flags: RwFlags,
+    ) -> io::Result<usize> {
+        use rustix::io::{pwritev2, Errno};
+
+        match pwritev2(self, iov_max(bufs), offset, to_rustix_rw_flags(flags)) {
+            // `NOTSUP` means the file doesn't support one of the flags.
+            Err(Errno::NOSYS) | Err(Errno::NOTSUP) => {
+                write_vectored_at_with_emulated_flags(self, bufs, offset, flags)
+            }
+            otherwise => Ok(otherwise?),
+        }
+    }
+
     fn append(&self, buf: &[u8]) -> io::Result<usize> {
         use rustix::fs::{fcntl_getfl, fcntl_setfl, seek, OFlags, SeekFrom};
         use rustix::io::write;
@@ -561,6 +1456,15 @@ impl<T: AsFilelike + IoExt> FileIoExt for T {
         use rustix::fs::{fcntl_getfl, fcntl_setfl, seek, OFlags, SeekFrom};
         use rustix::io::writev;
 
+        // Splitting the buffers into several writes would let other writes
+        // land between them, so don't.
+        if bufs.len() > IOV_MAX {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("more than {} buffers to append atomically", IOV_MAX),
+            ));
+        }
+
         // On Linux, use `pwritev2`.
         #[cfg(any(target_os = "android", target_os = "linux"))]
         {
@@ -601,6 +1505,144 @@ impl<T: AsFilelike + IoExt> FileIoExt for T {
         // [rust-lang/rust#59359]: https://github.com/rust-lang/rust/issues/59359.
         Ok(tell(self)?)
     }
+
+    #[inline]
+    fn sync_data(&self) -> io::Result<()> {
+        std::fs::File::sync_data(&self.as_filelike_view::<std::fs::File>())
+    }
+
+    #[inline]
+    fn sync_all(&self) -> io::Result<()> {
+        std::fs::File::sync_all(&self.as_filelike_view::<std::fs::File>())
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    fn sync_range(&self, offset: u64, len: u64, mode: SyncRangeMode) -> io::Result<()> {
+        use rustix::fd::AsRawFd;
+        use sync_file_range_sys::{
+            sync_file_range, SYNC_FILE_RANGE_WAIT_AFTER, SYNC_FILE_RANGE_WAIT_BEFORE,
+            SYNC_FILE_RANGE_WRITE,
+        };
+
+        let flags = match mode {
+            SyncRangeMode::Start => SYNC_FILE_RANGE_WRITE,
+            SyncRangeMode::Wait => {
+                SYNC_FILE_RANGE_WAIT_BEFORE | SYNC_FILE_RANGE_WRITE | SYNC_FILE_RANGE_WAIT_AFTER
+            }
+        };
+        let offset = offset
+            .try_into()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "offset out of range"))?;
+        // A length of zero means to the end of the file, which is also the
+        // best we can do for lengths which don't fit.
+        let len = len.try_into().unwrap_or(0);
+
+        // SAFETY: `sync_file_range` has no memory-safety preconditions, and
+        // the file descriptor is borrowed for the duration of the call.
+        let fd = self.as_filelike();
+        if unsafe { sync_file_range(fd.as_raw_fd(), offset, len, flags) } == 0 {
+            return Ok(());
+        }
+        match io::Error::last_os_error() {
+            err if err.raw_os_error() == Some(libc::ENOSYS) => self.sync_data(),
+            err => Err(err),
+        }
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    fn copy_range_to<Dst: AsFilelike + FileIoExt>(
+        &self,
+        dst: &Dst,
+        src_offset: u64,
+        dst_offset: u64,
+        len: u64,
+    ) -> io::Result<u64> {
+        use rustix::fs::copy_file_range;
+        use rustix::io::Errno;
+
+        let mut copied = 0;
+        while copied < len {
+            let src_pos = src_offset
+                .checked_add(copied)
+                .ok_or_else(|| io::Error::other("offset overflow"))?;
+            let dst_pos = dst_offset
+                .checked_add(copied)
+                .ok_or_else(|| io::Error::other("offset overflow"))?;
+            let chunk = (len - copied).min(usize::MAX as u64) as usize;
+            // Pass copies of the positions, since we track progress with
+            // `copied`, and need the originals if we fall back to copying.
+            match copy_file_range(
+                self,
+                Some(&mut { src_pos }),
+                dst.as_filelike(),
+                Some(&mut { dst_pos }),
+                chunk,
+            ) {
+                Ok(0) => break,
+                Ok(n) => copied += n as u64,
+                Err(Errno::INTR) => (),
+                // The kernel can't copy between these files, so copy the rest
+                // in userspace.
+                Err(Errno::XDEV) | Err(Errno::NOSYS) | Err(Errno::NOTSUP) => {
+                    return Ok(
+                        copied + copy_range_by_copying(self, dst, src_pos, dst_pos, len - copied)?
+                    );
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(copied)
+    }
+
+    #[cfg(any(
+        target_os = "android",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "illumos",
+        target_os = "ios",
+        target_os = "linux",
+        target_os = "macos",
+        target_os = "solaris",
+    ))]
+    fn seek_data(&self, offset: u64) -> io::Result<Option<u64>> {
+        let Ok(raw) = i64::try_from(offset) else {
+            return Ok(None);
+        };
+        match rustix::fs::seek(self, rustix::fs::SeekFrom::Data(raw)) {
+            Ok(pos) => Ok(Some(pos)),
+            Err(rustix::io::Errno::NXIO) => Ok(None),
+            // The filesystem doesn't support `SEEK_DATA`.
+            Err(rustix::io::Errno::INVAL) | Err(rustix::io::Errno::NOTSUP) => {
+                seek_data_whole_file(self, offset)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    #[cfg(any(
+        target_os = "android",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "illumos",
+        target_os = "ios",
+        target_os = "linux",
+        target_os = "macos",
+        target_os = "solaris",
+    ))]
+    fn seek_hole(&self, offset: u64) -> io::Result<Option<u64>> {
+        let Ok(raw) = i64::try_from(offset) else {
+            return Ok(None);
+        };
+        match rustix::fs::seek(self, rustix::fs::SeekFrom::Hole(raw)) {
+            Ok(pos) => Ok(Some(pos)),
+            Err(rustix::io::Errno::NXIO) => Ok(None),
+            // The filesystem doesn't support `SEEK_HOLE`.
+            Err(rustix::io::Errno::INVAL) | Err(rustix::io::Errno::NOTSUP) => {
+                seek_hole_whole_file(self, offset)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
 }
 
 #[cfg(windows)]
@@ -774,6 +1816,16 @@ impl FileIoExt for std::fs::File {
             SeekFrom::Current(0),
         )
     }
+
+    #[inline]
+    fn sync_data(&self) -> io::Result<()> {
+        fs::File::sync_data(self)
+    }
+
+    #[inline]
+    fn sync_all(&self) -> io::Result<()> {
+        fs::File::sync_all(self)
+    }
 }
 
 #[cfg(windows)]
@@ -886,6 +1938,16 @@ impl FileIoExt for cap_std::fs::File {
     fn stream_position(&self) -> io::Result<u64> {
         self.as_filelike_view::<std::fs::File>().stream_position()
     }
+
+    #[inline]
+    fn sync_data(&self) -> io::Result<()> {
+        self.as_filelike_view::<std::fs::File>().sync_data()
+    }
+
+    #[inline]
+    fn sync_all(&self) -> io::Result<()> {
+        self.as_filelike_view::<std::fs::File>().sync_all()
+    }
 }
 
 #[cfg(windows)]
@@ -998,6 +2060,16 @@ impl FileIoExt for cap_std::fs_utf8::File {
     fn stream_position(&self) -> io::Result<u64> {
         self.as_filelike_view::<std::fs::File>().stream_position()
     }
+
+    #[inline]
+    fn sync_data(&self) -> io::Result<()> {
+        self.as_filelike_view::<std::fs::File>().sync_data()
+    }
+
+    #[inline]
+    fn sync_all(&self) -> io::Result<()> {
+        self.as_filelike_view::<std::fs::File>().sync_all()
+    }
 }
 
 #[cfg(windows)]
@@ -1036,43 +2108,106 @@ unsafe fn _reopen_append(file: &fs::File) -> io::Result<fs::File> {
     file.reopen(cap_fs_ext::OpenOptions::new().append(true))
 }
 
-fn read_to_end_at(file: &std::fs::File, buf: &mut Vec<u8>, offset: u64) -> io::Result<usize> {
-    let len = match file.metadata()?.len().checked_sub(offset) {
-        None => return Ok(0),
-        Some(len) => len,
+/// `sync_file_range` and its flags. The libc crate doesn't declare them for
+/// Android, where bionic has had `sync_file_range` since API level 26.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+mod sync_file_range_sys {
+    #[cfg(target_os = "android")]
+    use libc::{c_int, c_uint, off64_t};
+    #[cfg(target_os = "linux")]
+    pub(super) use libc::{
+        sync_file_range, SYNC_FILE_RANGE_WAIT_AFTER, SYNC_FILE_RANGE_WAIT_BEFORE,
+        SYNC_FILE_RANGE_WRITE,
     };
 
-    // This initializes the buffer with zeros which is theoretically
-    // unnecessary, but current alternatives involve tricky `unsafe` code.
-    buf.resize(
-        (buf.len() as u64)
-            .saturating_add(len)
-            .try_into()
-            .unwrap_or(usize::MAX),
-        0_u8,
-    );
-    FileIoExt::read_exact_at(file, buf, offset)?;
-    Ok(len as usize)
+    #[cfg(target_os = "android")]
+    pub(super) const SYNC_FILE_RANGE_WAIT_BEFORE: c_uint = 1;
+    #[cfg(target_os = "android")]
+    pub(super) const SYNC_FILE_RANGE_WRITE: c_uint = 2;
+    #[cfg(target_os = "android")]
+    pub(super) const SYNC_FILE_RANGE_WAIT_AFTER: c_uint = 4;
+
+    #[cfg(target_os = "android")]
+    extern "C" {
+        pub(super) fn sync_file_range(
+            fd: c_int,
+            offset: off64_t,
+            nbytes: off64_t,
+            flags: c_uint,
+        ) -> c_int;
+    }
 }
 
-fn read_to_string_at(file: &std::fs::File, buf: &mut String, offset: u64) -> io::Result<usize> {
-    let len = match file.metadata()?.len().checked_sub(offset) {
-        None => return Ok(0),
-        Some(len) => len,
-    };
+/// The size of the buffer used to check for EOF when a read has filled the
+/// space the size hint suggested.
+const PROBE_SIZE: usize = 32;
+
+/// The minimum amount to grow the buffer by when the size hint is exhausted.
+const MIN_GROWTH: usize = 8 * 1024;
+
+fn read_to_end_at(file: &std::fs::File, buf: &mut Vec<u8>, mut offset: u64) -> io::Result<usize> {
+    let start_len = buf.len();
+
+    // The file size is only a hint. Files in procfs and sysfs report a size of
+    // zero, and files may grow or shrink while we read, so read until
+    // `read_at` reports EOF. When the hint is accurate, this takes one read to
+    // fill the buffer and one small read to confirm EOF. If the hint is too
+    // big to allocate, just grow as we go.
+    let hint = file.metadata()?.len().saturating_sub(offset);
+    if let Ok(hint) = usize::try_from(hint) {
+        let _ = buf.try_reserve_exact(hint);
+    }
+
+    // The number of bytes past `buf.len()` which earlier reads initialized,
+    // so that `read_buf_at` implementations which initialize the cursor don't
+    // do so again.
+    let mut initialized = 0;
+
+    loop {
+        if buf.len() == buf.capacity() {
+            // Probe with a small buffer, to avoid growing `buf` if we're at
+            // EOF.
+            let mut probe = [0_u8; PROBE_SIZE];
+            match FileIoExt::read_at(file, &mut probe, offset) {
+                Ok(0) => break,
+                Ok(n) => {
+                    buf.extend_from_slice(&probe[..n]);
+                    offset += n as u64;
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
 
-    // This temporary buffer is theoretically unnecessary, but eliminating it
-    // currently involves a bunch of `unsafe`.
-    let mut tmp = vec![0_u8; len.try_into().unwrap_or(usize::MAX)];
-    FileIoExt::read_exact_at(file, &mut tmp, offset)?;
-    let s = String::from_utf8(tmp).map_err(|_| {
-        io::Error::new(
-            io::ErrorKind::InvalidData,
-            "stream did not contain valid UTF-8",
-        )
-    })?;
-    buf.push_str(&s);
-    Ok(len as usize)
+            // Double the amount read so far, like `Vec` does.
+            buf.reserve((buf.len() - start_len).max(MIN_GROWTH));
+            initialized = 0;
+        }
+
+        let mut read_buf = BorrowedBuf::from(buf.spare_capacity_mut());
+        // SAFETY: These bytes were initialized by earlier reads.
+        unsafe { read_buf.set_init(initialized) };
+        match FileIoExt::read_buf_at(file, read_buf.unfilled(), offset) {
+            Ok(()) => (),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+        let n = read_buf.len();
+        if n == 0 {
+            break;
+        }
+        initialized = read_buf.init_len() - n;
+
+        // SAFETY: `BorrowedBuf` guarantees that its filled bytes are
+        // initialized.
+        unsafe { buf.set_len(buf.len() + n) };
+        offset += n as u64;
+    }
+
+    Ok(buf.len() - start_len)
+}
+
+fn read_to_string_at(file: &std::fs::File, buf: &mut String, offset: u64) -> io::Result<usize> {
+    append_to_string(buf, |vec| read_to_end_at(file, vec, offset))
 }
 
 fn _file_io_ext_can_be_trait_object(_: &dyn FileIoExt) {}
@@ -0,0 +1,507 @@
+//! The `FileLeaseExt` trait, and related types.
+//!
+//! The kernel reports a lease break by sending a signal to the lease holder.
+//! We install a handler for [`lease_break_signal`], which finds the lease for
+//! the file descriptor in the signal's `si_fd` and makes its eventfd
+//! readable. Leases are recorded in a list of slots which is only ever
+//! appended to, so that the handler can walk it without locking.
+
+use io_lifetimes::{AsFd, AsFilelike, BorrowedFd};
+use std::os::raw::{c_int, c_long, c_void};
+use std::ptr::{self, null_mut};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicPtr, Ordering};
+use std::sync::{Once, OnceLock};
+use std::{fmt, io, mem};
+
+#[cfg(all(target_env = "musl", target_arch = "hexagon"))]
+use libc::F_SETSIG;
+
+/// `F_SETSIG`, which the libc crate doesn't define for most Linux targets.
+///
+/// This is the value from `asm-generic/fcntl.h`. The only architecture which
+/// uses a different one is PA-RISC, which Rust doesn't support.
+#[cfg(not(all(target_env = "musl", target_arch = "hexagon")))]
+const F_SETSIG: c_int = 10;
+
+/// The size of the fields which precede the union in a `siginfo_t`, from
+/// `__ARCH_SI_PREAMBLE_SIZE` in `asm-generic/siginfo.h`. On 64-bit targets,
+/// it's padded so that the union is 8-byte aligned.
+#[cfg(target_pointer_width = "64")]
+const SI_PREAMBLE_SIZE: usize = 4 * mem::size_of::<c_int>();
+#[cfg(target_pointer_width = "32")]
+const SI_PREAMBLE_SIZE: usize = 3 * mem::size_of::<c_int>();
+
+/// The number of slots in each chunk of the lease list.
+const CHUNK_LEN: usize = 32;
+
+/// The kind of a file lease.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum LeaseKind {
+    /// A read lease, which is broken when another handle opens the file for
+    /// writing or truncates it.
+    Read,
+
+    /// A write lease, which is broken when another handle opens the file at
+    /// all.
+    Write,
+}
+
+/// Extension trait for taking file leases, using `F_SETLEASE`.
+///
+/// A lease lets a process cache a file's contents, and be told when another
+/// process wants to change them. When another handle opens the file in a way
+/// which conflicts with the lease, the open blocks until the lease holder
+/// releases or downgrades the lease, or until
+/// `/proc/sys/fs/lease-break-time` seconds have passed, whichever is first.
+///
+/// Both kinds of lease require the caller to own the file or have
+/// `CAP_LEASE`. A read lease requires the file to be open read-only, and can
+/// only be taken while no handles have the file open for writing. A write
+/// lease may be taken on a handle open for reading or writing, but only while
+/// no other handles have the file open at all. Otherwise, taking the lease
+/// fails with [`io::ErrorKind::WouldBlock`].
+///
+/// # Signal handling
+///
+/// Lease breaks are delivered with the signal returned by
+/// [`lease_break_signal`], which is `SIGRTMAX` unless a different one is
+/// chosen with [`set_lease_break_signal`]. The first time a lease is taken,
+/// a process-wide handler for that signal is installed, replacing any
+/// existing handler. Signals for file descriptors without leases from this
+/// crate are passed on to the previous handler, but applications and
+/// runtimes which use the signal for something else should choose a
+/// different one before taking any leases.
+pub trait FileLeaseExt {
+    /// Take a lease on the file.
+    ///
+    /// The lease is released when the returned guard is dropped.
+    ///
+    /// The first call installs a process-wide handler for
+    /// [`lease_break_signal`], taking it over from any other user; see
+    /// [Signal handling](FileLeaseExt#signal-handling).
+    fn lease(&self, kind: LeaseKind) -> io::Result<Lease<'_, Self>>
+    where
+        Self: AsFilelike + Sized;
+
+    /// Query the kind of lease currently held on the file, if any.
+    ///
+    /// While a lease is being broken, this returns the kind of lease it will
+    /// become: `None` for a lease which must be released, and
+    /// `Some(LeaseKind::Read)` for a write lease which may be downgraded.
+    fn get_lease(&self) -> io::Result<Option<LeaseKind>>
+    where
+        Self: AsFilelike;
+}
+
+impl<T> FileLeaseExt for T {
+    fn lease(&self, kind: LeaseKind) -> io::Result<Lease<'_, Self>>
+    where
+        Self: AsFilelike,
+    {
+        install_handler()?;
+
+        let fd = raw_fd(self);
+        // SAFETY: `F_SETSIG` has no memory-safety preconditions, and the file
+        // descriptor is borrowed for the duration of the call.
+        if unsafe { libc::fcntl(fd, F_SETSIG, lease_break_signal()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let slot = Slot::claim(fd)?;
+        if let Err(err) = set_lease(self, Some(kind)) {
+            slot.release();
+            return Err(err);
+        }
+        Ok(Lease {
+            file: self,
+            kind,
+            slot,
+        })
+    }
+
+    fn get_lease(&self) -> io::Result<Option<LeaseKind>>
+    where
+        Self: AsFilelike,
+    {
+        // SAFETY: `F_GETLEASE` has no memory-safety preconditions, and the
+        // file descriptor is borrowed for the duration of the call.
+        match unsafe { libc::fcntl(raw_fd(self), libc::F_GETLEASE) } {
+            -1 => Err(io::Error::last_os_error()),
+            libc::F_RDLCK => Ok(Some(LeaseKind::Read)),
+            libc::F_WRLCK => Ok(Some(LeaseKind::Write)),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// A lease on a file, acquired with [`FileLeaseExt::lease`], which is
+/// released when dropped.
+///
+/// The guard implements [`AsFd`], for a file descriptor which becomes
+/// readable when the lease is broken. It can be passed to [`poll`] or an
+/// event loop to wait for a break. Once broken, the lease holder should flush
+/// any state that depends on the file's contents, and then release the
+/// lease, or for a write lease being broken by a reader, downgrade it.
+///
+/// [`poll`]: crate::io::poll
+#[must_use = "the lease is released as soon as the guard is dropped"]
+pub struct Lease<'a, Filelike: AsFilelike> {
+    file: &'a Filelike,
+    kind: LeaseKind,
+    slot: &'static Slot,
+}
+
+impl<'a, Filelike: AsFilelike> Lease<'a, Filelike> {
+    /// Return the kind of this lease.
+    #[inline]
+    pub fn kind(&self) -> LeaseKind {
+        self.kind
+    }
+
+    /// Test whether another handle has started to break this lease.
+    pub fn is_broken(&self) -> io::Result<bool> {
+        let target = self.file.get_lease()?;
+        Ok(target != Some(self.kind))
+    }
+
+    /// Downgrade a write lease to a read lease.
+    ///
+    /// This resolves a break caused by another handle opening the file for
+    /// reading, while still allowing contents to be cached until a writer
+    /// comes along. It also clears the readiness of the guard's file
+    /// descriptor, so that it reports subsequent breaks. Downgrading a read
+    /// lease does nothing.
+    pub fn downgrade(&mut self) -> io::Result<()> {
+        if self.kind == LeaseKind::Read {
+            return Ok(());
+        }
+        set_lease(self.file, Some(LeaseKind::Read))?;
+        self.kind = LeaseKind::Read;
+        self.slot.drain();
+        Ok(())
+    }
+
+    /// Release this lease, reporting any error.
+    ///
+    /// Dropping the guard also releases the lease, but ignores errors.
+    pub fn release(self) -> io::Result<()> {
+        let result = set_lease(self.file, None);
+        self.slot.release();
+        mem::forget(self);
+        result
+    }
+}
+
+impl<'a, Filelike: AsFilelike> Drop for Lease<'a, Filelike> {
+    fn drop(&mut self) {
+        let _ = set_lease(self.file, None);
+        self.slot.release();
+    }
+}
+
+impl<'a, Filelike: AsFilelike> AsFd for Lease<'a, Filelike> {
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        // SAFETY: Slots' eventfds are created when a slot is first claimed,
+        // and never closed.
+        unsafe { BorrowedFd::borrow_raw(self.slot.eventfd.load(Ordering::Acquire)) }
+    }
+}
+
+impl<'a, Filelike: AsFilelike> fmt::Debug for Lease<'a, Filelike> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Lease")
+            .field("kind", &self.kind)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The signal chosen with `set_lease_break_signal`, or once a lease has
+/// been taken, the one in use. Zero means neither has happened yet.
+static SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+/// Return the signal used to deliver lease breaks, which is `SIGRTMAX`
+/// unless another was chosen with [`set_lease_break_signal`].
+///
+/// Applications which block signals should leave this one unblocked in at
+/// least one thread.
+pub fn lease_break_signal() -> c_int {
+    match SIGNAL.load(Ordering::Acquire) {
+        0 => libc::SIGRTMAX(),
+        signo => signo,
+    }
+}
+
+/// Choose the signal used to deliver lease breaks, instead of `SIGRTMAX`.
+///
+/// This must be a real-time signal, between `SIGRTMIN` and `SIGRTMAX`, so
+/// that breaks of several leases are queued rather than merged. It can only
+/// be chosen once, and before any leases are taken; otherwise this fails,
+/// unless `signo` is the signal already in use.
+pub fn set_lease_break_signal(signo: c_int) -> io::Result<()> {
+    if !(libc::SIGRTMIN()..=libc::SIGRTMAX()).contains(&signo) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the lease break signal must be a real-time signal",
+        ));
+    }
+    match SIGNAL.compare_exchange(0, signo, Ordering::AcqRel, Ordering::Acquire) {
+        Ok(_) => Ok(()),
+        Err(current) if current == signo => Ok(()),
+        Err(_) => Err(io::Error::other(
+            "the lease break signal has already been chosen",
+        )),
+    }
+}
+
+/// Return the signal to use for lease breaks, fixing it if it hasn't been
+/// chosen yet.
+fn fix_lease_break_signal() -> c_int {
+    match SIGNAL.compare_exchange(0, libc::SIGRTMAX(), Ordering::AcqRel, Ordering::Acquire) {
+        Ok(_) => libc::SIGRTMAX(),
+        Err(current) => current,
+    }
+}
+
+/// A place to record a lease: the file descriptor it's on, and an eventfd to
+/// make readable when it's broken.
+struct Slot {
+    claimed: AtomicBool,
+    fd: AtomicI32,
+    eventfd: AtomicI32,
+}
+
+/// A chunk of the list of slots.
+struct Chunk {
+    slots: [Slot; CHUNK_LEN],
+    next: AtomicPtr<Chunk>,
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_SLOT: Slot = Slot {
+    claimed: AtomicBool::new(false),
+    fd: AtomicI32::new(-1),
+    eventfd: AtomicI32::new(-1),
+};
+
+static SLOTS: Chunk = Chunk {
+    slots: [EMPTY_SLOT; CHUNK_LEN],
+    next: AtomicPtr::new(null_mut()),
+};
+
+/// The handler which was installed for `lease_break_signal` before ours.
+static PREVIOUS_HANDLER: OnceLock<libc::sigaction> = OnceLock::new();
+
+impl Slot {
+    /// Claim a free slot, adding a chunk to the list if need be, and record
+    /// `fd` in it.
+    fn claim(fd: c_int) -> io::Result<&'static Self> {
+        let mut chunk = &SLOTS;
+        let slot = loop {
+            if let Some(slot) = chunk.slots.iter().find(|slot| {
+                slot.claimed
+                    .compare_exchange(false, true, Ordering::AcqRel, Ordering::Relaxed)
+                    .is_ok()
+            }) {
+                break slot;
+            }
+            let mut next = chunk.next.load(Ordering::Acquire);
+            if next.is_null() {
+                let new = Box::into_raw(Box::new(Chunk {
+                    slots: [EMPTY_SLOT; CHUNK_LEN],
+                    next: AtomicPtr::new(null_mut()),
+                }));
+                next = match chunk.next.compare_exchange(
+                    null_mut(),
+                    new,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                ) {
+                    Ok(_) => new,
+                    Err(existing) => {
+                        // SAFETY: We just allocated `new`, and lost the race
+                        // to publish it.
+                        drop(unsafe { Box::from_raw(new) });
+                        existing
+                    }
+                };
+            }
+            // SAFETY: Chunks are never freed once published.
+            chunk = unsafe { &*next };
+        };
+
+        if slot.eventfd.load(Ordering::Acquire) == -1 {
+            use rustix::event::{eventfd, EventfdFlags};
+            use rustix::fd::IntoRawFd;
+
+            match eventfd(0, EventfdFlags::CLOEXEC | EventfdFlags::NONBLOCK) {
+                Ok(eventfd) => slot.eventfd.store(eventfd.into_raw_fd(), Ordering::Release),
+                Err(err) => {
+                    slot.claimed.store(false, Ordering::Release);
+                    return Err(err.into());
+                }
+            }
+        }
+        slot.drain();
+        slot.fd.store(fd, Ordering::Release);
+        Ok(slot)
+    }
+
+    /// Clear any pending break notification.
+    fn drain(&self) {
+        let mut buf = [0_u8; 8];
+        // SAFETY: The eventfd is never closed, and the buffer is valid for
+        // the duration of the call.
+        unsafe {
+            libc::read(
+                self.eventfd.load(Ordering::Acquire),
+                buf.as_mut_ptr().cast(),
+                buf.len(),
+            );
+        }
+    }
+
+    /// Return the slot to the free list. The eventfd is kept for reuse.
+    fn release(&self) {
+        self.fd.store(-1, Ordering::Release);
+        self.claimed.store(false, Ordering::Release);
+    }
+}
+
+/// The layout of a `siginfo_t` for a `SIGPOLL`-style signal, which is what
+/// `F_SETSIG` arranges for the kernel to send. The libc crate doesn't provide
+/// an accessor for `si_fd`.
+#[repr(C)]
+struct SigPoll {
+    si_signo: c_int,
+    si_errno: c_int,
+    si_code: c_int,
+    #[cfg(target_pointer_width = "64")]
+    _pad: c_int,
+    si_band: c_long,
+    si_fd: c_int,
+}
+
+// Check that `si_fd` is where the kernel's `_sigpoll` puts it: after the
+// preamble and `si_band`.
+const _: () =
+    assert!(mem::offset_of!(SigPoll, si_fd) == SI_PREAMBLE_SIZE + mem::size_of::<c_long>());
+const _: () = assert!(mem::size_of::<SigPoll>() <= mem::size_of::<libc::siginfo_t>());
+
+extern "C" fn handle_lease_break(signo: c_int, info: *mut libc::siginfo_t, context: *mut c_void) {
+    // SAFETY: The kernel passes a valid `siginfo_t`, which has the
+    // `SIGPOLL` layout for signals requested with `F_SETSIG`.
+    let fd = unsafe { (*info.cast::<SigPoll>()).si_fd };
+
+    // Only async-signal-safe operations may be used here. Preserve `errno`
+    // for the interrupted code, since `write` may clobber it.
+    // SAFETY: `__errno_location` returns a pointer to this thread's `errno`.
+    let errno = unsafe { *libc::__errno_location() };
+
+    let mut found = false;
+    let mut chunk: *const Chunk = &SLOTS;
+    while !chunk.is_null() {
+        // SAFETY: Chunks are never freed once published.
+        let chunk_ref = unsafe { &*chunk };
+        for slot in &chunk_ref.slots {
+            if slot.fd.load(Ordering::Acquire) == fd {
+                found = true;
+                let one = 1_u64;
+                // SAFETY: The eventfd is never closed, and `one` is valid for
+                // the duration of the call.
+                unsafe {
+                    libc::write(
+                        slot.eventfd.load(Ordering::Acquire),
+                        ptr::addr_of!(one).cast(),
+                        mem::size_of::<u64>(),
+                    );
+                }
+            }
+        }
+        chunk = chunk_ref.next.load(Ordering::Acquire);
+    }
+
+    // SAFETY: See above.
+    unsafe { *libc::__errno_location() = errno };
+
+    if !found {
+        if let Some(previous) = PREVIOUS_HANDLER.get() {
+            // SAFETY: We're forwarding the arguments we were passed to the
+            // handler which would have received them if not for us.
+            unsafe {
+                if previous.sa_flags & libc::SA_SIGINFO != 0 {
+                    let handler: extern "C" fn(c_int, *mut libc::siginfo_t, *mut c_void) =
+                        mem::transmute(previous.sa_sigaction);
+                    handler(signo, info, context);
+                } else if previous.sa_sigaction != libc::SIG_DFL
+                    && previous.sa_sigaction != libc::SIG_IGN
+                {
+                    let handler: extern "C" fn(c_int) = mem::transmute(previous.sa_sigaction);
+                    handler(signo);
+                }
+            }
+        }
+    }
+}
+
+/// Install `handle_lease_break`, if it hasn't been already.
+fn install_handler() -> io::Result<()> {
+    static INSTALL: Once = Once::new();
+    static RESULT: AtomicI32 = AtomicI32::new(0);
+
+    INSTALL.call_once(|| {
+        let signo = fix_lease_break_signal();
+        // SAFETY: `sigaction` is a plain C struct, and the handler we install
+        // only performs async-signal-safe operations.
+        unsafe {
+            let mut action: libc::sigaction = mem::zeroed();
+            action.sa_sigaction = handle_lease_break as *const () as usize;
+            action.sa_flags = libc::SA_SIGINFO | libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            let mut previous: libc::sigaction = mem::zeroed();
+            // Record the previous handler before ours can run.
+            if libc::sigaction(signo, ptr::null(), &mut previous) != 0
+                || PREVIOUS_HANDLER.set(previous).is_err()
+                || libc::sigaction(signo, &action, ptr::null_mut()) != 0
+            {
+                RESULT.store(*libc::__errno_location(), Ordering::Relaxed);
+            }
+        }
+    });
+
+    match RESULT.load(Ordering::Relaxed) {
+        0 => Ok(()),
+        errno => Err(io::Error::from_raw_os_error(errno)),
+    }
+}
+
+/// Set, or with a `kind` of `None` release, a lease on `file`.
+fn set_lease<Filelike: AsFilelike>(file: &Filelike, kind: Option<LeaseKind>) -> io::Result<()> {
+    let arg = match kind {
+        Some(LeaseKind::Read) => libc::F_RDLCK,
+        Some(LeaseKind::Write) => libc::F_WRLCK,
+        None => libc::F_UNLCK,
+    };
+    // SAFETY: `F_SETLEASE` has no memory-safety preconditions, and the file
+    // descriptor is borrowed for the duration of the call.
+    if unsafe { libc::fcntl(raw_fd(file), libc::F_SETLEASE, arg) } == 0 {
+        return Ok(());
+    }
+    let err = io::Error::last_os_error();
+    Err(match err.raw_os_error() {
+        // The filesystem doesn't support leases, or they're disabled with
+        // `/proc/sys/fs/leases-enable`.
+        Some(libc::EINVAL) => io::Error::new(
+            io::ErrorKind::Unsupported,
+            "file leases are not supported on this file",
+        ),
+        _ => err,
+    })
+}
+
+#[inline]
+fn raw_fd<Filelike: AsFilelike>(file: &Filelike) -> c_int {
+    use rustix::fd::AsRawFd;
+
+    file.as_filelike().as_raw_fd()
+}
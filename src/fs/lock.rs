@@ -0,0 +1,473 @@
+//! The `FileLockExt` trait, and related types.
+
+use io_lifetimes::AsFilelike;
+use std::{fmt, io, mem};
+
+/// The kind of an advisory lock.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum LockKind {
+    /// A shared lock. Any number of shared locks may be held at once.
+    Shared,
+
+    /// An exclusive lock, which conflicts with all other locks.
+    Exclusive,
+}
+
+/// Extension trait for taking advisory locks on files.
+///
+/// Locks are associated with the open file description, rather than with the
+/// file descriptor or the process. They're shared with handles obtained with
+/// `dup` or `try_clone`, closing one of those handles doesn't release them,
+/// and they conflict with locks taken through independently opened handles,
+/// including handles in the same process.
+///
+/// Guards aren't reference-counted. On Unix, an open file description holds
+/// at most one lock on each byte, so locking through a handle which already
+/// holds a lock, or through a `dup` of it, converts the existing lock to the
+/// new kind rather than adding another, and dropping any one of the guards
+/// releases the lock for all of them. For example, taking a shared lock,
+/// upgrading it by taking an exclusive lock through a second guard, and then
+/// dropping the first guard leaves the file unlocked. Similarly, dropping a
+/// range lock unlocks its range even where it overlaps other range locks
+/// held through the same open file description. To avoid surprises, hold at
+/// most one guard per open file description at a time.
+///
+/// Whole-file locks use `flock`. Range locks use open file description locks
+/// (`F_OFD_SETLK` and `F_OFD_SETLKW`), which are available on Linux and
+/// Android; on other Unix platforms, range locks return
+/// [`io::ErrorKind::Unsupported`]. On Linux, the two kinds of lock are
+/// independent and don't conflict with each other. As with other `fcntl`
+/// locks, a shared range lock requires the file to be open for reading, and
+/// an exclusive range lock requires it to be open for writing.
+///
+/// On Windows, both kinds of lock use `LockFileEx`, and a whole-file lock is
+/// a lock on every possible byte. Windows locks are mandatory rather than
+/// advisory: a shared lock prevents writes, including through the handle
+/// which holds it, and an exclusive lock prevents reads and writes through
+/// other handles. Windows locks also don't merge, so locking a range which
+/// overlaps a range already locked through the same handle fails, and each
+/// guard releases only its own lock.
+pub trait FileLockExt {
+    /// Acquire a lock on the whole file, waiting until any conflicting locks
+    /// are released.
+    ///
+    /// The lock is released when the returned guard is dropped.
+    fn lock_file(&self, kind: LockKind) -> io::Result<FileLock<'_, Self>>
+    where
+        Self: AsFilelike + Sized;
+
+    /// Acquire a lock on the whole file, failing with
+    /// [`io::ErrorKind::WouldBlock`] if a conflicting lock is held.
+    ///
+    /// The lock is released when the returned guard is dropped.
+    fn try_lock_file(&self, kind: LockKind) -> io::Result<FileLock<'_, Self>>
+    where
+        Self: AsFilelike + Sized;
+
+    /// Acquire a lock on `len` bytes starting at `offset`, waiting until any
+    /// conflicting locks are released.
+    ///
+    /// A `len` of zero locks through the end of the file, including any bytes
+    /// later appended. The lock is released when the returned guard is
+    /// dropped.
+    fn lock_range(&self, offset: u64, len: u64, kind: LockKind) -> io::Result<RangeLock<'_, Self>>
+    where
+        Self: AsFilelike + Sized;
+
+    /// Acquire a lock on `len` bytes starting at `offset`, failing with
+    /// [`io::ErrorKind::WouldBlock`] if a conflicting lock is held.
+    ///
+    /// A `len` of zero locks through the end of the file, including any bytes
+    /// later appended. The lock is released when the returned guard is
+    /// dropped.
+    fn try_lock_range(
+        &self,
+        offset: u64,
+        len: u64,
+        kind: LockKind,
+    ) -> io::Result<RangeLock<'_, Self>>
+    where
+        Self: AsFilelike + Sized;
+}
+
+impl<T> FileLockExt for T {
+    fn lock_file(&self, kind: LockKind) -> io::Result<FileLock<'_, Self>>
+    where
+        Self: AsFilelike,
+    {
+        lock_whole(self, kind, true)?;
+        Ok(FileLock { file: self, kind })
+    }
+
+    fn try_lock_file(&self, kind: LockKind) -> io::Result<FileLock<'_, Self>>
+    where
+        Self: AsFilelike,
+    {
+        lock_whole(self, kind, false)?;
+        Ok(FileLock { file: self, kind })
+    }
+
+    fn lock_range(&self, offset: u64, len: u64, kind: LockKind) -> io::Result<RangeLock<'_, Self>>
+    where
+        Self: AsFilelike,
+    {
+        lock_range(self, offset, len, Some(kind), true)?;
+        Ok(RangeLock {
+            file: self,
+            offset,
+            len,
+            kind,
+        })
+    }
+
+    fn try_lock_range(
+        &self,
+        offset: u64,
+        len: u64,
+        kind: LockKind,
+    ) -> io::Result<RangeLock<'_, Self>>
+    where
+        Self: AsFilelike,
+    {
+        lock_range(self, offset, len, Some(kind), false)?;
+        Ok(RangeLock {
+            file: self,
+            offset,
+            len,
+            kind,
+        })
+    }
+}
+
+/// A lock on a whole file, acquired with [`FileLockExt::lock_file`] or
+/// [`FileLockExt::try_lock_file`], which is released when dropped.
+#[must_use = "the lock is released as soon as the guard is dropped"]
+pub struct FileLock<'a, Filelike: AsFilelike> {
+    file: &'a Filelike,
+    kind: LockKind,
+}
+
+impl<'a, Filelike: AsFilelike> FileLock<'a, Filelike> {
+    /// Return the kind of this lock.
+    #[inline]
+    pub fn kind(&self) -> LockKind {
+        self.kind
+    }
+
+    /// Release this lock, reporting any error.
+    ///
+    /// Dropping the guard also releases the lock, but ignores errors.
+    pub fn unlock(self) -> io::Result<()> {
+        let result = unlock_whole(self.file);
+        mem::forget(self);
+        result
+    }
+}
+
+impl<'a, Filelike: AsFilelike> Drop for FileLock<'a, Filelike> {
+    fn drop(&mut self) {
+        let _ = unlock_whole(self.file);
+    }
+}
+
+impl<'a, Filelike: AsFilelike> fmt::Debug for FileLock<'a, Filelike> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileLock")
+            .field("kind", &self.kind)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A lock on a range of a file, acquired with [`FileLockExt::lock_range`] or
+/// [`FileLockExt::try_lock_range`], which is released when dropped.
+#[must_use = "the lock is released as soon as the guard is dropped"]
+pub struct RangeLock<'a, Filelike: AsFilelike> {
+    file: &'a Filelike,
+    offset: u64,
+    len: u64,
+    kind: LockKind,
+}
+
+// A `len` of zero means through the end of the file, so there's no sensible
+// `is_empty`.
+#[allow(clippy::len_without_is_empty)]
+impl<'a, Filelike: AsFilelike> RangeLock<'a, Filelike> {
+    /// Return the offset of the start of the locked range.
+    #[inline]
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Return the length of the locked range, where zero means through the
+    /// end of the file.
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Return the kind of this lock.
+    #[inline]
+    pub fn kind(&self) -> LockKind {
+        self.kind
+    }
+
+    /// Release this lock, reporting any error.
+    ///
+    /// Dropping the guard also releases the lock, but ignores errors.
+    pub fn unlock(self) -> io::Result<()> {
+        let result = lock_range(self.file, self.offset, self.len, None, false);
+        mem::forget(self);
+        result
+    }
+}
+
+impl<'a, Filelike: AsFilelike> Drop for RangeLock<'a, Filelike> {
+    fn drop(&mut self) {
+        let _ = lock_range(self.file, self.offset, self.len, None, false);
+    }
+}
+
+impl<'a, Filelike: AsFilelike> fmt::Debug for RangeLock<'a, Filelike> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RangeLock")
+            .field("offset", &self.offset)
+            .field("len", &self.len)
+            .field("kind", &self.kind)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(not(any(
+    windows,
+    target_os = "espidf",
+    target_os = "solaris",
+    target_os = "vita",
+    target_os = "wasi"
+)))]
+fn lock_whole<Filelike: AsFilelike>(
+    file: &Filelike,
+    kind: LockKind,
+    block: bool,
+) -> io::Result<()> {
+    use rustix::fs::{flock, FlockOperation};
+
+    let operation = match (kind, block) {
+        (LockKind::Shared, true) => FlockOperation::LockShared,
+        (LockKind::Exclusive, true) => FlockOperation::LockExclusive,
+        (LockKind::Shared, false) => FlockOperation::NonBlockingLockShared,
+        (LockKind::Exclusive, false) => FlockOperation::NonBlockingLockExclusive,
+    };
+    loop {
+        match flock(file.as_filelike(), operation) {
+            Err(rustix::io::Errno::INTR) => continue,
+            otherwise => return Ok(otherwise?),
+        }
+    }
+}
+
+#[cfg(not(any(
+    windows,
+    target_os = "espidf",
+    target_os = "solaris",
+    target_os = "vita",
+    target_os = "wasi"
+)))]
+fn unlock_whole<Filelike: AsFilelike>(file: &Filelike) -> io::Result<()> {
+    use rustix::fs::{flock, FlockOperation};
+
+    Ok(flock(file.as_filelike(), FlockOperation::Unlock)?)
+}
+
+#[cfg(any(
+    target_os = "espidf",
+    target_os = "solaris",
+    target_os = "vita",
+    target_os = "wasi"
+))]
+fn lock_whole<Filelike: AsFilelike>(
+    _file: &Filelike,
+    _kind: LockKind,
+    _block: bool,
+) -> io::Result<()> {
+    Err(unsupported(
+        "whole-file locks are not supported on this platform",
+    ))
+}
+
+#[cfg(any(
+    target_os = "espidf",
+    target_os = "solaris",
+    target_os = "vita",
+    target_os = "wasi"
+))]
+fn unlock_whole<Filelike: AsFilelike>(_file: &Filelike) -> io::Result<()> {
+    Err(unsupported(
+        "whole-file locks are not supported on this platform",
+    ))
+}
+
+#[cfg(windows)]
+fn lock_whole<Filelike: AsFilelike>(
+    file: &Filelike,
+    kind: LockKind,
+    block: bool,
+) -> io::Result<()> {
+    lock_range(file, 0, 0, Some(kind), block)
+}
+
+#[cfg(windows)]
+fn unlock_whole<Filelike: AsFilelike>(file: &Filelike) -> io::Result<()> {
+    lock_range(file, 0, 0, None, false)
+}
+
+/// Lock, or with a `kind` of `None` unlock, a range of `file`.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn lock_range<Filelike: AsFilelike>(
+    file: &Filelike,
+    offset: u64,
+    len: u64,
+    kind: Option<LockKind>,
+    block: bool,
+) -> io::Result<()> {
+    use rustix::fd::AsRawFd;
+
+    // SAFETY: `flock` is a plain C struct, and open file description locks
+    // require `l_pid` to be zero.
+    let mut lock: libc::flock = unsafe { mem::zeroed() };
+    lock.l_type = match kind {
+        Some(LockKind::Shared) => libc::F_RDLCK,
+        Some(LockKind::Exclusive) => libc::F_WRLCK,
+        None => libc::F_UNLCK,
+    } as _;
+    lock.l_whence = libc::SEEK_SET as _;
+    // Check the range here, so that an `EINVAL` from the kernel can only mean
+    // it doesn't support the command.
+    lock.l_start = offset
+        .try_into()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "offset out of range"))?;
+    lock.l_len = len
+        .try_into()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "length out of range"))?;
+    if len != 0 && lock.l_start.checked_add(lock.l_len - 1).is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "range extends past the largest file offset",
+        ));
+    }
+    let cmd = if block {
+        libc::F_OFD_SETLKW
+    } else {
+        libc::F_OFD_SETLK
+    };
+
+    // SAFETY: `lock` is a valid `flock` for the duration of the call, and
+    // the file descriptor is borrowed for the duration of the call.
+    let fd = file.as_filelike();
+    loop {
+        if unsafe { libc::fcntl(fd.as_raw_fd(), cmd, &lock) } == 0 {
+            return Ok(());
+        }
+        let err = io::Error::last_os_error();
+        return Err(match err.raw_os_error() {
+            Some(libc::EINTR) => continue,
+            // POSIX permits reporting a conflicting lock with either `EACCES`
+            // or `EAGAIN`.
+            Some(libc::EACCES) => io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "the range is locked by another open file description",
+            ),
+            // The range is valid, so this means the kernel is older than 3.15
+            // and doesn't recognize the `F_OFD_*` commands.
+            Some(libc::EINVAL) => unsupported("open file description locks are not supported"),
+            _ => err,
+        });
+    }
+}
+
+#[cfg(not(any(windows, target_os = "android", target_os = "linux")))]
+fn lock_range<Filelike: AsFilelike>(
+    _file: &Filelike,
+    _offset: u64,
+    _len: u64,
+    _kind: Option<LockKind>,
+    _block: bool,
+) -> io::Result<()> {
+    Err(unsupported(
+        "range locks are not supported on this platform",
+    ))
+}
+
+/// Lock, or with a `kind` of `None` unlock, a range of `file`.
+#[cfg(windows)]
+fn lock_range<Filelike: AsFilelike>(
+    file: &Filelike,
+    offset: u64,
+    len: u64,
+    kind: Option<LockKind>,
+    block: bool,
+) -> io::Result<()> {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::Foundation::ERROR_LOCK_VIOLATION;
+    use windows_sys::Win32::Storage::FileSystem::{
+        LockFileEx, UnlockFileEx, LOCKFILE_EXCLUSIVE_LOCK, LOCKFILE_FAIL_IMMEDIATELY,
+    };
+    use windows_sys::Win32::System::IO::OVERLAPPED;
+
+    // Windows has no way to say "through the end of the file", so lock every
+    // byte from `offset` on instead.
+    let len = if len == 0 { u64::MAX - offset } else { len };
+
+    // SAFETY: `OVERLAPPED` is a plain C struct.
+    let mut overlapped: OVERLAPPED = unsafe { mem::zeroed() };
+    overlapped.Anonymous.Anonymous.Offset = offset as u32;
+    overlapped.Anonymous.Anonymous.OffsetHigh = (offset >> 32) as u32;
+
+    // SAFETY: `overlapped` is valid for the duration of the call, and the
+    // handle is borrowed for the duration of the call. The handle is
+    // synchronous, so the call completes before returning.
+    let handle = file.as_filelike();
+    let res = unsafe {
+        match kind {
+            Some(kind) => {
+                let mut flags = 0;
+                if kind == LockKind::Exclusive {
+                    flags |= LOCKFILE_EXCLUSIVE_LOCK;
+                }
+                if !block {
+                    flags |= LOCKFILE_FAIL_IMMEDIATELY;
+                }
+                LockFileEx(
+                    handle.as_raw_handle() as _,
+                    flags,
+                    0,
+                    len as u32,
+                    (len >> 32) as u32,
+                    &mut overlapped,
+                )
+            }
+            None => UnlockFileEx(
+                handle.as_raw_handle() as _,
+                0,
+                len as u32,
+                (len >> 32) as u32,
+                &mut overlapped,
+            ),
+        }
+    };
+    if res == 0 {
+        let err = io::Error::last_os_error();
+        return Err(match err.raw_os_error() {
+            Some(code) if code == ERROR_LOCK_VIOLATION as i32 => io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "the range is locked by another handle",
+            ),
+            _ => err,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn unsupported(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, msg)
+}
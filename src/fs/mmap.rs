@@ -0,0 +1,397 @@
+//! The `MappedRegion` type, for memory-mapped views of files.
+
+use crate::fs::Advice;
+use io_lifetimes::AsFilelike;
+use std::ops::Deref;
+use std::ptr::NonNull;
+use std::{fmt, io, slice};
+
+/// How a [`MappedRegion`] maps its file.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum MapMode {
+    /// The region may only be read. The file must be open for reading.
+    ReadOnly,
+
+    /// The region may be read and written, and writes are carried through
+    /// to the file, where other mappings and readers can see them. The file
+    /// must be open for reading and writing.
+    Shared,
+
+    /// The region may be read and written, but writes are private to the
+    /// region, and are never carried through to the file. The file must be
+    /// open for reading.
+    Private,
+}
+
+/// A memory-mapped view of a range of a file.
+///
+/// The mapping is independent of the handle it was created from, and stays
+/// valid after the handle is closed. It's unmapped when the `MappedRegion` is
+/// dropped.
+///
+/// # Safety
+///
+/// A mapping exposes the file's contents as ordinary memory, so changes to
+/// the file made through other handles, other mappings, or other processes
+/// are visible through the slices a `MappedRegion` hands out, violating the
+/// guarantee that a `&[u8]` doesn't change while it's borrowed. Worse, if the
+/// file is truncated so that it no longer covers part of the region, then
+/// accessing that part raises `SIGBUS` on Unix platforms, or an access
+/// violation on Windows, which terminates the process.
+///
+/// For these reasons, [`MappedRegion::new`] is `unsafe`, and its caller must
+/// ensure that for as long as the region exists, the file is not truncated
+/// below the end of the region, and the mapped range is not modified other
+/// than through the region itself. Typically this means using a file that
+/// is private to the process, or coordinating with other processes with
+/// [`FileLockExt`].
+///
+/// [`FileLockExt`]: crate::fs::FileLockExt
+pub struct MappedRegion {
+    /// The start of the mapping, which is aligned to the platform's mapping
+    /// granularity, and may be before the start of the region.
+    base: NonNull<u8>,
+    /// The distance from `base` to the start of the region.
+    delta: usize,
+    len: usize,
+    mode: MapMode,
+}
+
+// SAFETY: `MappedRegion` owns its mapping, and only hands out access to it
+// through `&self` and `&mut self`, like a `Vec<u8>`.
+unsafe impl Send for MappedRegion {}
+unsafe impl Sync for MappedRegion {}
+
+impl MappedRegion {
+    /// Map `len` bytes of `file`, starting at `offset`.
+    ///
+    /// `offset` need not be aligned; the mapping is extended downward to the
+    /// platform's mapping granularity as needed.
+    ///
+    /// # Safety
+    ///
+    /// For as long as the returned region exists, the file must not be
+    /// truncated below `offset + len`, and the mapped range must not be
+    /// modified other than through the region. See the [type-level
+    /// documentation] for details.
+    ///
+    /// [type-level documentation]: MappedRegion#safety
+    pub unsafe fn new<Filelike: AsFilelike>(
+        file: &Filelike,
+        offset: u64,
+        len: usize,
+        mode: MapMode,
+    ) -> io::Result<Self> {
+        let delta = (offset % granularity() as u64) as usize;
+        if len == 0 {
+            return Ok(Self {
+                base: NonNull::dangling(),
+                delta: 0,
+                len: 0,
+                mode,
+            });
+        }
+        let map_len = len.checked_add(delta).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "mapping length out of range")
+        })?;
+        let base = map(file, offset - delta as u64, map_len, mode)?;
+        Ok(Self {
+            base,
+            delta,
+            len,
+            mode,
+        })
+    }
+
+    /// Return the mode of this mapping.
+    #[inline]
+    pub fn mode(&self) -> MapMode {
+        self.mode
+    }
+
+    /// Return the length of the region, in bytes.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Test whether the region is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return a mutable slice of the region's contents, or `None` if the
+    /// region is [`MapMode::ReadOnly`].
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> Option<&mut [u8]> {
+        match self.mode {
+            MapMode::ReadOnly => None,
+            // SAFETY: The region is mapped writable, and we have exclusive
+            // access to it.
+            MapMode::Shared | MapMode::Private => {
+                Some(unsafe { slice::from_raw_parts_mut(self.start(), self.len) })
+            }
+        }
+    }
+
+    /// Write any changes in the region back to the file, and wait for them to
+    /// reach the device.
+    ///
+    /// This is a no-op for regions which aren't [`MapMode::Shared`].
+    #[inline]
+    pub fn flush(&self) -> io::Result<()> {
+        self.flush_range(0, self.len)
+    }
+
+    /// Write any changes in `len` bytes of the region starting at `offset`
+    /// back to the file, and wait for them to reach the device, like `msync`
+    /// with `MS_SYNC`.
+    ///
+    /// This is a no-op for regions which aren't [`MapMode::Shared`]. On
+    /// Windows, this writes the changes to the file, but doesn't wait for
+    /// them to reach the device; use [`FileIoExt::sync_data`] on a handle to
+    /// the file for that.
+    ///
+    /// [`FileIoExt::sync_data`]: crate::fs::FileIoExt::sync_data
+    pub fn flush_range(&self, offset: usize, len: usize) -> io::Result<()> {
+        self.check_range(offset, len)?;
+        if self.mode != MapMode::Shared || len == 0 {
+            return Ok(());
+        }
+        let (ptr, len) = self.page_range(offset, len);
+        flush(ptr, len)
+    }
+
+    /// Announce the expected access pattern of `len` bytes of the region
+    /// starting at `offset`, like `madvise`.
+    ///
+    /// Advice which has no counterpart on the platform is ignored.
+    pub fn advise(&self, offset: usize, len: usize, advice: Advice) -> io::Result<()> {
+        self.check_range(offset, len)?;
+        if len == 0 {
+            return Ok(());
+        }
+        let (ptr, len) = self.page_range(offset, len);
+        advise(ptr, len, advice)
+    }
+
+    /// Return a pointer to the start of the region.
+    #[inline]
+    fn start(&self) -> *mut u8 {
+        // SAFETY: `delta` is within the mapping.
+        unsafe { self.base.as_ptr().add(self.delta) }
+    }
+
+    fn check_range(&self, offset: usize, len: usize) -> io::Result<()> {
+        match offset.checked_add(len) {
+            Some(end) if end <= self.len => Ok(()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "range is outside of the mapped region",
+            )),
+        }
+    }
+
+    /// Extend a range of the region down to the start of a page, as `msync`
+    /// and `madvise` require.
+    fn page_range(&self, offset: usize, len: usize) -> (*mut u8, usize) {
+        let offset = offset + self.delta;
+        let aligned = offset - offset % page_size();
+        // SAFETY: `aligned` is within the mapping.
+        let ptr = unsafe { self.base.as_ptr().add(aligned) };
+        (ptr, len + (offset - aligned))
+    }
+}
+
+impl Deref for MappedRegion {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        // SAFETY: The region is mapped readable, and the caller of `new`
+        // promised that it isn't modified other than through `self`.
+        unsafe { slice::from_raw_parts(self.start(), self.len) }
+    }
+}
+
+impl Drop for MappedRegion {
+    fn drop(&mut self) {
+        if self.len != 0 {
+            // SAFETY: We own the mapping, and nothing borrows it any more.
+            unsafe { unmap(self.base, self.delta + self.len) };
+        }
+    }
+}
+
+impl fmt::Debug for MappedRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MappedRegion")
+            .field("ptr", &self.start())
+            .field("len", &self.len)
+            .field("mode", &self.mode)
+            .finish()
+    }
+}
+
+#[cfg(not(windows))]
+#[inline]
+fn granularity() -> usize {
+    page_size()
+}
+
+#[cfg(not(windows))]
+#[inline]
+fn page_size() -> usize {
+    rustix::param::page_size()
+}
+
+#[cfg(not(windows))]
+unsafe fn map<Filelike: AsFilelike>(
+    file: &Filelike,
+    offset: u64,
+    len: usize,
+    mode: MapMode,
+) -> io::Result<NonNull<u8>> {
+    use rustix::mm::{mmap, MapFlags, ProtFlags};
+
+    let (prot, flags) = match mode {
+        MapMode::ReadOnly => (ProtFlags::READ, MapFlags::SHARED),
+        MapMode::Shared => (ProtFlags::READ | ProtFlags::WRITE, MapFlags::SHARED),
+        MapMode::Private => (ProtFlags::READ | ProtFlags::WRITE, MapFlags::PRIVATE),
+    };
+    let ptr = mmap(
+        std::ptr::null_mut(),
+        len,
+        prot,
+        flags,
+        file.as_filelike(),
+        offset,
+    )?;
+    Ok(NonNull::new(ptr.cast()).unwrap())
+}
+
+#[cfg(not(windows))]
+unsafe fn unmap(base: NonNull<u8>, len: usize) {
+    let _ = rustix::mm::munmap(base.as_ptr().cast(), len);
+}
+
+#[cfg(not(windows))]
+fn flush(ptr: *mut u8, len: usize) -> io::Result<()> {
+    use rustix::mm::{msync, MsyncFlags};
+
+    // SAFETY: The range is within a mapping owned by the caller.
+    Ok(unsafe { msync(ptr.cast(), len, MsyncFlags::SYNC) }?)
+}
+
+#[cfg(not(windows))]
+fn advise(ptr: *mut u8, len: usize, advice: Advice) -> io::Result<()> {
+    use rustix::mm::madvise;
+
+    let advice = match advice {
+        Advice::Normal => rustix::mm::Advice::Normal,
+        Advice::Sequential => rustix::mm::Advice::Sequential,
+        Advice::Random => rustix::mm::Advice::Random,
+        Advice::WillNeed => rustix::mm::Advice::WillNeed,
+        // This is `POSIX_MADV_DONTNEED`, rather than Linux's `MADV_DONTNEED`,
+        // which would discard changes in private mappings.
+        Advice::DontNeed => rustix::mm::Advice::DontNeed,
+        // `madvise` has no counterpart to `POSIX_FADV_NOREUSE`.
+        Advice::NoReuse => return Ok(()),
+    };
+    // SAFETY: The range is within a mapping owned by the caller, and none of
+    // these kinds of advice change its contents.
+    Ok(unsafe { madvise(ptr.cast(), len, advice) }?)
+}
+
+#[cfg(windows)]
+fn granularity() -> usize {
+    system_info().dwAllocationGranularity as usize
+}
+
+#[cfg(windows)]
+fn page_size() -> usize {
+    system_info().dwPageSize as usize
+}
+
+#[cfg(windows)]
+fn system_info() -> windows_sys::Win32::System::SystemInformation::SYSTEM_INFO {
+    use windows_sys::Win32::System::SystemInformation::GetSystemInfo;
+
+    // SAFETY: `SYSTEM_INFO` is a plain C struct, which `GetSystemInfo`
+    // initializes.
+    unsafe {
+        let mut info = std::mem::zeroed();
+        GetSystemInfo(&mut info);
+        info
+    }
+}
+
+#[cfg(windows)]
+unsafe fn map<Filelike: AsFilelike>(
+    file: &Filelike,
+    offset: u64,
+    len: usize,
+    mode: MapMode,
+) -> io::Result<NonNull<u8>> {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::Memory::{
+        CreateFileMappingW, MapViewOfFile, FILE_MAP_COPY, FILE_MAP_READ, FILE_MAP_WRITE,
+        PAGE_READONLY, PAGE_READWRITE, PAGE_WRITECOPY,
+    };
+
+    let (protect, access) = match mode {
+        MapMode::ReadOnly => (PAGE_READONLY, FILE_MAP_READ),
+        MapMode::Shared => (PAGE_READWRITE, FILE_MAP_READ | FILE_MAP_WRITE),
+        MapMode::Private => (PAGE_WRITECOPY, FILE_MAP_COPY),
+    };
+
+    // A maximum size of zero makes the mapping object the size of the file.
+    let mapping = CreateFileMappingW(
+        file.as_filelike().as_raw_handle() as _,
+        std::ptr::null(),
+        protect,
+        0,
+        0,
+        std::ptr::null(),
+    );
+    // `HANDLE` is an integer in older versions of windows-sys.
+    if mapping as usize == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let view = MapViewOfFile(mapping, access, (offset >> 32) as u32, offset as u32, len);
+    // The view keeps the mapping object alive.
+    let err = io::Error::last_os_error();
+    CloseHandle(mapping);
+    match NonNull::new(view.Value.cast()) {
+        Some(ptr) => Ok(ptr),
+        None => Err(err),
+    }
+}
+
+#[cfg(windows)]
+unsafe fn unmap(base: NonNull<u8>, _len: usize) {
+    use windows_sys::Win32::System::Memory::{UnmapViewOfFile, MEMORY_MAPPED_VIEW_ADDRESS};
+
+    UnmapViewOfFile(MEMORY_MAPPED_VIEW_ADDRESS {
+        Value: base.as_ptr().cast(),
+    });
+}
+
+#[cfg(windows)]
+fn flush(ptr: *mut u8, len: usize) -> io::Result<()> {
+    use windows_sys::Win32::System::Memory::FlushViewOfFile;
+
+    // SAFETY: The range is within a mapping owned by the caller.
+    if unsafe { FlushViewOfFile(ptr.cast(), len) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn advise(_ptr: *mut u8, _len: usize, _advice: Advice) -> io::Result<()> {
+    // TODO: Do something with the advice.
+    Ok(())
+}
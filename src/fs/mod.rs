@@ -1,10 +1,37 @@
 //! Filesystem extension traits.
 
+#[cfg(any(feature = "async-std", feature = "tokio_impls"))]
+mod async_file_io_ext;
+#[cfg(all(
+    any(target_os = "android", target_os = "linux"),
+    feature = "use_io_uring"
+))]
+mod batch;
+mod direct;
+mod extents;
 mod fd_flags;
 mod file_io_ext;
+#[cfg(target_os = "linux")]
+mod lease;
+mod lock;
+mod mmap;
 
+#[cfg(any(feature = "async-std", feature = "tokio_impls"))]
+pub use async_file_io_ext::AsyncFileIoExt;
+#[cfg(all(
+    any(target_os = "android", target_os = "linux"),
+    feature = "use_io_uring"
+))]
+pub use batch::Batch;
+pub use direct::{direct_io_alignment, AlignedBuf};
+pub use extents::{Extent, ExtentKind, Extents};
 pub use fd_flags::{FdFlags, GetSetFdFlags, SetFdFlags};
-pub use file_io_ext::{Advice, FileIoExt};
+pub(crate) use file_io_ext::COPY_BUF_SIZE;
+pub use file_io_ext::{Advice, FileIoExt, RwFlags, SyncRangeMode};
+#[cfg(target_os = "linux")]
+pub use lease::{lease_break_signal, set_lease_break_signal, FileLeaseExt, Lease, LeaseKind};
+pub use lock::{FileLock, FileLockExt, LockKind, RangeLock};
+pub use mmap::{MapMode, MappedRegion};
 
 // Windows quirks:
 //  - Open dir can't be renamed or deleted
@@ -12,5 +39,3 @@ pub use file_io_ext::{Advice, FileIoExt};
 //  - seek past end of file files unspec rather than zero
 
 // TODO: test that remove_dir can remove symlink_dirs, and remove_file files.
-
-// TODO: poll
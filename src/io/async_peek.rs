@@ -0,0 +1,105 @@
+//! The `AsyncPeek` trait, and related utilities and impls.
+
+use std::future::Future;
+use std::io;
+#[cfg(all(unix, feature = "cap_async_std_impls"))]
+use {crate::io::async_read_ready::Readiness, io_lifetimes::AsSocketlike};
+
+/// An async counterpart of [`Peek`], for async-std and tokio sockets.
+///
+/// [`Peek`]: crate::io::Peek
+pub trait AsyncPeek {
+    /// Reads data from a stream without consuming it; subsequent reads will
+    /// re-read the data.
+    ///
+    /// This waits, without blocking the executor, until at least one byte
+    /// can be peeked, and returns `Ok(0)` if `buf` is empty or the peer has
+    /// closed the stream.
+    fn peek(&mut self, buf: &mut [u8]) -> impl Future<Output = io::Result<usize>> + Send;
+}
+
+#[cfg(feature = "cap_async_std_impls")]
+impl AsyncPeek for async_std::net::TcpStream {
+    #[inline]
+    async fn peek(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        async_std::net::TcpStream::peek(self, buf).await
+    }
+}
+
+#[cfg(all(unix, feature = "cap_async_std_impls"))]
+impl AsyncPeek for async_std::os::unix::net::UnixStream {
+    #[inline]
+    async fn peek(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        peek(self, buf).await
+    }
+}
+
+#[cfg(feature = "cap_async_std_impls")]
+impl AsyncPeek for cap_async_std::net::TcpStream {
+    #[inline]
+    async fn peek(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        cap_async_std::net::TcpStream::peek(self, buf).await
+    }
+}
+
+#[cfg(all(unix, feature = "cap_async_std_impls"))]
+impl AsyncPeek for cap_async_std::os::unix::net::UnixStream {
+    #[inline]
+    async fn peek(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        peek(self, buf).await
+    }
+}
+
+#[cfg(feature = "tokio_impls")]
+impl AsyncPeek for tokio::net::TcpStream {
+    #[inline]
+    async fn peek(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        tokio::net::TcpStream::peek(self, buf).await
+    }
+}
+
+#[cfg(all(unix, feature = "tokio_impls"))]
+impl AsyncPeek for tokio::net::UnixStream {
+    async fn peek(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        use rustix::net::{recv, RecvFlags};
+        use tokio::io::Interest;
+
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            self.readable().await?;
+            match self.try_io(Interest::READABLE, || {
+                Ok(recv(
+                    &*self,
+                    &mut *buf,
+                    RecvFlags::PEEK | RecvFlags::DONTWAIT,
+                )?)
+            }) {
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => (),
+                otherwise => return otherwise,
+            }
+        }
+    }
+}
+
+#[cfg(all(unix, feature = "cap_async_std_impls"))]
+async fn peek<Socketlike: AsSocketlike + Sync>(
+    socketlike: &Socketlike,
+    buf: &mut [u8],
+) -> io::Result<usize> {
+    use rustix::io::Errno;
+    use rustix::net::{recv, RecvFlags};
+
+    if buf.is_empty() {
+        return Ok(0);
+    }
+    let mut readiness = Readiness::new();
+    loop {
+        match recv(socketlike, buf, RecvFlags::PEEK | RecvFlags::DONTWAIT) {
+            Err(Errno::AGAIN) => readiness.readable(socketlike).await?,
+            Err(Errno::INTR) => (),
+            otherwise => return Ok(otherwise?),
+        }
+    }
+}
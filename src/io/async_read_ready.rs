@@ -0,0 +1,236 @@
+//! The `AsyncReadReady` trait, and related utilities and impls.
+
+use std::future::Future;
+use std::io;
+#[cfg(feature = "tokio_impls")]
+use tokio::io::Interest;
+#[cfg(feature = "cap_async_std_impls")]
+use {crate::io::ReadReady, io_lifetimes::AsSocketlike};
+#[cfg(all(unix, feature = "cap_async_std_impls"))]
+use {
+    async_io::Async,
+    rustix::io::Errno,
+    rustix::net::{recv, RecvFlags},
+    std::os::unix::net::UnixStream,
+};
+
+/// An async counterpart of [`ReadReady`], for async-std and tokio sockets
+/// and pipes.
+///
+/// [`ReadReady`]: crate::io::ReadReady
+pub trait AsyncReadReady {
+    /// Wait until data can be read from this handle, and return the number
+    /// of bytes which can be read immediately, as
+    /// [`ReadReady::num_ready_bytes`] would.
+    ///
+    /// This waits without blocking the executor. It returns `Ok(0)` if the
+    /// peer has closed the stream.
+    ///
+    /// [`ReadReady::num_ready_bytes`]: crate::io::ReadReady::num_ready_bytes
+    fn wait_ready(&self) -> impl Future<Output = io::Result<u64>> + Send;
+}
+
+#[cfg(feature = "cap_async_std_impls")]
+impl AsyncReadReady for async_std::net::TcpStream {
+    async fn wait_ready(&self) -> io::Result<u64> {
+        loop {
+            // Peeking waits using the stream's own reactor registration.
+            if self.peek(&mut [0_u8]).await? == 0 {
+                return Ok(0);
+            }
+            match ready_bytes(self)? {
+                // Another reader drained the stream since the peek.
+                0 => (),
+                n => return Ok(n),
+            }
+        }
+    }
+}
+
+#[cfg(all(unix, feature = "cap_async_std_impls"))]
+impl AsyncReadReady for async_std::os::unix::net::UnixStream {
+    #[inline]
+    async fn wait_ready(&self) -> io::Result<u64> {
+        wait_ready(self).await
+    }
+}
+
+#[cfg(feature = "cap_async_std_impls")]
+impl AsyncReadReady for cap_async_std::net::TcpStream {
+    async fn wait_ready(&self) -> io::Result<u64> {
+        loop {
+            // Peeking waits using the stream's own reactor registration.
+            if self.peek(&mut [0_u8]).await? == 0 {
+                return Ok(0);
+            }
+            match ready_bytes(self)? {
+                // Another reader drained the stream since the peek.
+                0 => (),
+                n => return Ok(n),
+            }
+        }
+    }
+}
+
+#[cfg(all(unix, feature = "cap_async_std_impls"))]
+impl AsyncReadReady for cap_async_std::os::unix::net::UnixStream {
+    #[inline]
+    async fn wait_ready(&self) -> io::Result<u64> {
+        wait_ready(self).await
+    }
+}
+
+#[cfg(feature = "tokio_impls")]
+impl AsyncReadReady for tokio::net::TcpStream {
+    async fn wait_ready(&self) -> io::Result<u64> {
+        loop {
+            self.readable().await?;
+            match self.try_io(Interest::READABLE, || tokio_ready_now(self)) {
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => (),
+                otherwise => return otherwise,
+            }
+        }
+    }
+}
+
+#[cfg(all(unix, feature = "tokio_impls"))]
+impl AsyncReadReady for tokio::net::UnixStream {
+    async fn wait_ready(&self) -> io::Result<u64> {
+        loop {
+            self.readable().await?;
+            match self.try_io(Interest::READABLE, || tokio_ready_now(self)) {
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => (),
+                otherwise => return otherwise,
+            }
+        }
+    }
+}
+
+#[cfg(all(unix, feature = "tokio_impls"))]
+impl AsyncReadReady for tokio::net::unix::pipe::Receiver {
+    async fn wait_ready(&self) -> io::Result<u64> {
+        loop {
+            self.readable().await?;
+            match self.try_io(|| tokio_ready_now(self)) {
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => (),
+                otherwise => return otherwise,
+            }
+        }
+    }
+}
+
+#[cfg(feature = "cap_async_std_impls")]
+fn ready_bytes<Socketlike: AsSocketlike>(socketlike: &Socketlike) -> io::Result<u64> {
+    socketlike
+        .as_socketlike_view::<std::net::TcpStream>()
+        .num_ready_bytes()
+}
+
+#[cfg(all(unix, feature = "cap_async_std_impls"))]
+async fn wait_ready<Socketlike: AsSocketlike + Sync>(socketlike: &Socketlike) -> io::Result<u64> {
+    let mut readiness = Readiness::new();
+    loop {
+        match ready_bytes(socketlike)? {
+            0 => (),
+            n => return Ok(n),
+        }
+        // A count of zero means either that there's nothing to read yet, or
+        // that the peer has closed the stream; a peek tells them apart.
+        match recv(
+            socketlike,
+            &mut [0_u8],
+            RecvFlags::PEEK | RecvFlags::DONTWAIT,
+        ) {
+            Ok(0) => return Ok(0),
+            // Data arrived since the count was taken.
+            Ok(_) => (),
+            Err(Errno::AGAIN) => readiness.readable(socketlike).await?,
+            Err(Errno::INTR) => (),
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+/// Waits for an async-std Unix-domain socket to become readable.
+///
+/// The socket is already registered with the async-std reactor, which
+/// doesn't expose its readiness, so this registers a duplicate of it, the
+/// first time it's needed. The registration is reused for the rest of the
+/// operation, but not beyond it, since holding the duplicate open would keep
+/// the connection open after its owner closes it.
+#[cfg(all(unix, feature = "cap_async_std_impls"))]
+pub(crate) struct Readiness(Option<Async<UnixStream>>);
+
+#[cfg(all(unix, feature = "cap_async_std_impls"))]
+impl Readiness {
+    pub(crate) const fn new() -> Self {
+        Self(None)
+    }
+
+    /// Wait until `socketlike` is readable, or has reached the end of the
+    /// stream.
+    pub(crate) async fn readable<Socketlike: AsSocketlike>(
+        &mut self,
+        socketlike: &Socketlike,
+    ) -> io::Result<()> {
+        let watcher = match &mut self.0 {
+            Some(watcher) => watcher,
+            None => {
+                let dup = socketlike.as_socketlike_view::<UnixStream>().try_clone()?;
+                // async-std sockets are already in non-blocking mode.
+                self.0.insert(Async::new_nonblocking(dup)?)
+            }
+        };
+        watcher.readable().await
+    }
+}
+
+/// Return the number of bytes ready to be read from `handle`, or fail with
+/// `io::ErrorKind::WouldBlock` if it's neither readable nor at the end of the
+/// stream, so that tokio clears its cached readiness.
+#[cfg(all(not(windows), feature = "tokio_impls"))]
+fn tokio_ready_now<Handle>(handle: &Handle) -> io::Result<u64>
+where
+    Handle: crate::io::ReadReady + io_lifetimes::AsFilelike,
+{
+    use crate::io::{poll, PollFd, PollFlags};
+
+    let n = handle.num_ready_bytes()?;
+    if n != 0 {
+        return Ok(n);
+    }
+    let mut fds = [PollFd::from_filelike(handle, PollFlags::IN)];
+    loop {
+        match poll(&mut fds, Some(std::time::Duration::ZERO)) {
+            Ok(0) => return Err(io::ErrorKind::WouldBlock.into()),
+            Ok(_) => return Ok(0),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Return the number of bytes ready to be read from `handle`, or fail with
+/// `io::ErrorKind::WouldBlock` if it's neither readable nor at the end of the
+/// stream, so that tokio clears its cached readiness.
+#[cfg(all(windows, feature = "tokio_impls"))]
+fn tokio_ready_now<Handle>(handle: &Handle) -> io::Result<u64>
+where
+    Handle: crate::io::ReadReady + io_lifetimes::AsSocketlike,
+{
+    use crate::io::{poll, PollFd, PollFlags};
+
+    let n = handle.num_ready_bytes()?;
+    if n != 0 {
+        return Ok(n);
+    }
+    let mut fds = [PollFd::from_socketlike(handle, PollFlags::IN)];
+    loop {
+        match poll(&mut fds, Some(std::time::Duration::ZERO)) {
+            Ok(0) => return Err(io::ErrorKind::WouldBlock.into()),
+            Ok(_) => return Ok(0),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+            Err(err) => return Err(err),
+        }
+    }
+}
@@ -0,0 +1,284 @@
+//! The `BorrowedBuf` and `BorrowedCursor` types, for reading into
+//! uninitialized memory.
+//!
+//! These follow the design of the unstable `std::io::BorrowedBuf` and
+//! `std::io::BorrowedCursor`.
+
+use std::mem::MaybeUninit;
+use std::{cmp, fmt, ptr};
+
+/// A borrowed byte buffer which is incrementally filled and initialized.
+///
+/// The buffer is divided into three regions: the filled region, which holds
+/// data that has been read; the initialized but unfilled region, which holds
+/// bytes that have been initialized but not yet filled with data; and the
+/// uninitialized region. Tracking the initialized region lets reads into
+/// the same memory avoid initializing it more than once, and lets reads from
+/// sources which can write to uninitialized memory, such as the `read` and
+/// `pread` system calls, avoid initializing it at all.
+///
+/// Data is written into the buffer through a [`BorrowedCursor`], obtained
+/// from [`BorrowedBuf::unfilled`].
+pub struct BorrowedBuf<'data> {
+    buf: &'data mut [MaybeUninit<u8>],
+    filled: usize,
+    init: usize,
+}
+
+impl<'data> From<&'data mut [u8]> for BorrowedBuf<'data> {
+    /// Create a new `BorrowedBuf` from a fully initialized slice.
+    #[inline]
+    fn from(slice: &'data mut [u8]) -> Self {
+        let init = slice.len();
+        Self {
+            // SAFETY: Initialized bytes are valid `MaybeUninit<u8>`s, and we
+            // never de-initialize bytes.
+            buf: unsafe { &mut *(slice as *mut [u8] as *mut [MaybeUninit<u8>]) },
+            filled: 0,
+            init,
+        }
+    }
+}
+
+impl<'data> From<&'data mut [MaybeUninit<u8>]> for BorrowedBuf<'data> {
+    /// Create a new `BorrowedBuf` from an uninitialized slice.
+    #[inline]
+    fn from(buf: &'data mut [MaybeUninit<u8>]) -> Self {
+        Self {
+            buf,
+            filled: 0,
+            init: 0,
+        }
+    }
+}
+
+impl<'data> BorrowedBuf<'data> {
+    /// Return the total capacity of the buffer.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Return the length of the filled part of the buffer.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.filled
+    }
+
+    /// Test whether the filled part of the buffer is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.filled == 0
+    }
+
+    /// Return the length of the initialized part of the buffer, which
+    /// includes the filled part.
+    #[inline]
+    pub fn init_len(&self) -> usize {
+        self.init
+    }
+
+    /// Return a shared reference to the filled part of the buffer.
+    #[inline]
+    pub fn filled(&self) -> &[u8] {
+        // SAFETY: The filled part of the buffer is initialized.
+        unsafe { &*(&self.buf[..self.filled] as *const [MaybeUninit<u8>] as *const [u8]) }
+    }
+
+    /// Return a mutable reference to the filled part of the buffer.
+    #[inline]
+    pub fn filled_mut(&mut self) -> &mut [u8] {
+        // SAFETY: The filled part of the buffer is initialized.
+        unsafe { &mut *(&mut self.buf[..self.filled] as *mut [MaybeUninit<u8>] as *mut [u8]) }
+    }
+
+    /// Return a cursor over the unfilled part of the buffer.
+    #[inline]
+    pub fn unfilled<'this>(&'this mut self) -> BorrowedCursor<'this> {
+        BorrowedCursor {
+            start: self.filled,
+            buf: &mut *self.buf,
+            filled: &mut self.filled,
+            init: &mut self.init,
+        }
+    }
+
+    /// Clear the buffer, resetting the filled part to empty.
+    ///
+    /// The initialized part of the buffer is unchanged.
+    #[inline]
+    pub fn clear(&mut self) -> &mut Self {
+        self.filled = 0;
+        self
+    }
+
+    /// Assert that the first `n` bytes of the buffer are initialized.
+    ///
+    /// `BorrowedBuf` assumes that bytes are never de-initialized, so this
+    /// method does nothing when called with fewer bytes than are already
+    /// known to be initialized.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the first `n` bytes of the buffer have
+    /// already been initialized.
+    #[inline]
+    pub unsafe fn set_init(&mut self, n: usize) -> &mut Self {
+        self.init = cmp::max(self.init, n);
+        self
+    }
+}
+
+impl<'data> fmt::Debug for BorrowedBuf<'data> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BorrowedBuf")
+            .field("init", &self.init)
+            .field("filled", &self.filled)
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+/// A writeable view of the unfilled part of a [`BorrowedBuf`].
+///
+/// Data written through the cursor is appended to the filled part of the
+/// underlying buffer. A cursor can only be advanced, so it can't be used to
+/// overwrite data which has already been filled.
+pub struct BorrowedCursor<'a> {
+    buf: &'a mut [MaybeUninit<u8>],
+    filled: &'a mut usize,
+    init: &'a mut usize,
+    /// The value of `filled` when the cursor was created.
+    start: usize,
+}
+
+impl<'a> BorrowedCursor<'a> {
+    /// Reborrow this cursor, so that it can be passed to a function by value
+    /// and then used again afterwards.
+    #[inline]
+    pub fn reborrow<'this>(&'this mut self) -> BorrowedCursor<'this> {
+        BorrowedCursor {
+            buf: &mut *self.buf,
+            filled: &mut *self.filled,
+            init: &mut *self.init,
+            start: self.start,
+        }
+    }
+
+    /// Return the available space in the cursor.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.buf.len() - *self.filled
+    }
+
+    /// Return the number of bytes written to the underlying buffer through
+    /// this cursor, or through cursors reborrowed from it.
+    #[inline]
+    pub fn written(&self) -> usize {
+        *self.filled - self.start
+    }
+
+    /// Return a mutable reference to the initialized part of the cursor.
+    #[inline]
+    pub fn init_mut(&mut self) -> &mut [u8] {
+        let (filled, init) = (*self.filled, *self.init);
+        // SAFETY: The bytes between `filled` and `init` are initialized.
+        unsafe { &mut *(&mut self.buf[filled..init] as *mut [MaybeUninit<u8>] as *mut [u8]) }
+    }
+
+    /// Return a mutable reference to the whole cursor, including its
+    /// uninitialized part.
+    ///
+    /// # Safety
+    ///
+    /// The caller must not de-initialize any bytes of the returned slice.
+    #[inline]
+    pub unsafe fn as_mut(&mut self) -> &mut [MaybeUninit<u8>] {
+        &mut self.buf[*self.filled..]
+    }
+
+    /// Advance the cursor by `n` bytes, marking them as filled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if fewer than `n` bytes of the cursor are initialized.
+    #[inline]
+    pub fn advance(&mut self, n: usize) -> &mut Self {
+        assert!(
+            n <= *self.init - *self.filled,
+            "advancing a cursor past its initialized part"
+        );
+        *self.filled += n;
+        self
+    }
+
+    /// Advance the cursor by `n` bytes, marking them as filled, and as
+    /// initialized.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the first `n` bytes of the cursor have
+    /// been initialized.
+    #[inline]
+    pub unsafe fn advance_unchecked(&mut self, n: usize) -> &mut Self {
+        *self.filled += n;
+        *self.init = cmp::max(*self.init, *self.filled);
+        self
+    }
+
+    /// Initialize all of the bytes in the cursor, zero-filling any which
+    /// aren't initialized already.
+    #[inline]
+    pub fn ensure_init(&mut self) -> &mut Self {
+        let uninit = &mut self.buf[*self.init..];
+        // SAFETY: We're writing zeros to memory we have exclusive access to.
+        unsafe { ptr::write_bytes(uninit.as_mut_ptr(), 0, uninit.len()) };
+        *self.init = self.buf.len();
+        self
+    }
+
+    /// Assert that the first `n` bytes of the cursor are initialized.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the first `n` bytes of the cursor have
+    /// already been initialized.
+    #[inline]
+    pub unsafe fn set_init(&mut self, n: usize) -> &mut Self {
+        *self.init = cmp::max(*self.init, *self.filled + n);
+        self
+    }
+
+    /// Append `buf` to the cursor, advancing it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf` is longer than the cursor's capacity.
+    #[inline]
+    pub fn append(&mut self, buf: &[u8]) {
+        assert!(
+            self.capacity() >= buf.len(),
+            "appending past a cursor's end"
+        );
+        let filled = *self.filled;
+        // SAFETY: We have exclusive access to the destination, which has
+        // room for `buf`, and we initialize what we write to.
+        unsafe {
+            ptr::copy_nonoverlapping(
+                buf.as_ptr(),
+                self.buf[filled..].as_mut_ptr().cast::<u8>(),
+                buf.len(),
+            );
+            self.advance_unchecked(buf.len());
+        }
+    }
+}
+
+impl<'a> fmt::Debug for BorrowedCursor<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BorrowedCursor")
+            .field("written", &self.written())
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
@@ -0,0 +1,261 @@
+//! The `IoCancelExt` trait, and related types.
+
+use crate::io::{poll, IoExt, PollFd, PollFlags};
+use io_lifetimes::{AsFilelike, AsSocketlike};
+use rustix::fd::OwnedFd;
+use rustix::fs::{fstat, FileType};
+use rustix::io::{read, write, Errno};
+use std::io::{self, IoSliceMut};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The shared state of a [`CancelToken`] and its [`Canceller`]s.
+///
+/// On Linux, this is an eventfd, which stays readable from when it's
+/// cancelled until it's reset. Elsewhere, it's a self-pipe, which stays
+/// readable until it's drained.
+struct Inner {
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    eventfd: OwnedFd,
+    #[cfg(not(any(target_os = "android", target_os = "linux")))]
+    reader: OwnedFd,
+    #[cfg(not(any(target_os = "android", target_os = "linux")))]
+    writer: OwnedFd,
+}
+
+/// A token which can be passed to [`IoCancelExt`] methods, and which causes
+/// them to fail with `io::ErrorKind::Interrupted` once it's cancelled by a
+/// [`Canceller`].
+///
+/// Once cancelled, a token stays cancelled until it's [reset].
+///
+/// This is not available on Windows.
+///
+/// [reset]: CancelToken::reset
+#[derive(Clone)]
+pub struct CancelToken {
+    inner: Arc<Inner>,
+}
+
+/// A handle which cancels a [`CancelToken`], typically from another thread.
+#[derive(Clone)]
+pub struct Canceller {
+    inner: Arc<Inner>,
+}
+
+impl CancelToken {
+    /// Create a new `CancelToken`, which isn't cancelled.
+    pub fn new() -> io::Result<Self> {
+        #[cfg(any(target_os = "android", target_os = "linux"))]
+        let inner = {
+            use rustix::event::{eventfd, EventfdFlags};
+
+            Inner {
+                eventfd: eventfd(0, EventfdFlags::CLOEXEC | EventfdFlags::NONBLOCK)?,
+            }
+        };
+
+        #[cfg(not(any(target_os = "android", target_os = "linux")))]
+        let inner = {
+            use rustix::fs::{fcntl_getfl, fcntl_setfl, OFlags};
+            use rustix::io::{fcntl_setfd, FdFlags};
+            use rustix::pipe::pipe;
+
+            let (reader, writer) = pipe()?;
+            for fd in [&reader, &writer] {
+                fcntl_setfd(fd, FdFlags::CLOEXEC)?;
+                fcntl_setfl(fd, fcntl_getfl(fd)? | OFlags::NONBLOCK)?;
+            }
+            Inner { reader, writer }
+        };
+
+        Ok(Self {
+            inner: Arc::new(inner),
+        })
+    }
+
+    /// Return a [`Canceller`] which cancels this token.
+    #[inline]
+    pub fn canceller(&self) -> Canceller {
+        Canceller {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+
+    /// Test whether this token has been cancelled.
+    pub fn is_cancelled(&self) -> io::Result<bool> {
+        let mut fds = [PollFd::from_filelike(self.inner.readable(), PollFlags::IN)];
+        loop {
+            match poll(&mut fds, Some(Duration::ZERO)) {
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+                otherwise => return otherwise.map(|n| n != 0),
+            }
+        }
+    }
+
+    /// Return this token to the un-cancelled state.
+    pub fn reset(&self) -> io::Result<()> {
+        let mut buf = [0_u8; 8];
+        loop {
+            match read(self.inner.readable(), &mut buf) {
+                // On Linux, one read drains the eventfd.
+                #[cfg(any(target_os = "android", target_os = "linux"))]
+                Ok(_) => return Ok(()),
+                #[cfg(not(any(target_os = "android", target_os = "linux")))]
+                Ok(_) => (),
+                Err(Errno::AGAIN) => return Ok(()),
+                Err(Errno::INTR) => (),
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+impl Canceller {
+    /// Cancel the token, causing current and future [`IoCancelExt`] calls
+    /// using it to fail with `io::ErrorKind::Interrupted`.
+    pub fn cancel(&self) -> io::Result<()> {
+        #[cfg(any(target_os = "android", target_os = "linux"))]
+        let result = write(&self.inner.eventfd, &1_u64.to_ne_bytes());
+        #[cfg(not(any(target_os = "android", target_os = "linux")))]
+        let result = write(&self.inner.writer, &[0]);
+
+        match result {
+            // The eventfd counter is saturated, or the pipe is full, so the
+            // token is already cancelled.
+            Err(Errno::AGAIN) => Ok(()),
+            otherwise => otherwise.map(|_| ()).map_err(Into::into),
+        }
+    }
+}
+
+impl Inner {
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    #[inline]
+    fn readable(&self) -> &OwnedFd {
+        &self.eventfd
+    }
+
+    #[cfg(not(any(target_os = "android", target_os = "linux")))]
+    #[inline]
+    fn readable(&self) -> &OwnedFd {
+        &self.reader
+    }
+}
+
+/// Extension trait for I/O handles that supports reads and writes which can
+/// be cancelled from another thread.
+///
+/// These wait for either the handle or a [`CancelToken`] to become ready,
+/// and then perform the I/O with [`IoExt::try_read`] and related functions,
+/// so a cancellation doesn't close or otherwise affect the handle, which
+/// remains usable afterwards.
+///
+/// Regular files always report that they're ready, so if a read or write on
+/// one would need to wait for the disk, it's performed as a blocking call,
+/// which can't be cancelled.
+///
+/// Cancellation is reported as `io::ErrorKind::Interrupted`. Since that's
+/// also the error kind which many loops retry on, callers which use such
+/// loops should check [`CancelToken::is_cancelled`].
+///
+/// This is not available on Windows.
+pub trait IoCancelExt: IoExt {
+    /// Like [`IoExt::read`], but fails with `io::ErrorKind::Interrupted` if
+    /// `token` is cancelled before any data arrives.
+    fn read_cancellable(&self, buf: &mut [u8], token: &CancelToken) -> io::Result<usize>;
+
+    /// Like [`IoExt::read_vectored`], but fails with
+    /// `io::ErrorKind::Interrupted` if `token` is cancelled before any data
+    /// arrives.
+    fn read_vectored_cancellable(
+        &self,
+        bufs: &mut [IoSliceMut],
+        token: &CancelToken,
+    ) -> io::Result<usize>;
+
+    /// Like [`IoExt::write`], but fails with `io::ErrorKind::Interrupted` if
+    /// `token` is cancelled before anything can be written.
+    fn write_cancellable(&self, buf: &[u8], token: &CancelToken) -> io::Result<usize>;
+}
+
+/// Implement `IoCancelExt` for any type which implements `AsRawFd`.
+impl<T: AsFilelike + AsSocketlike> IoCancelExt for T {
+    fn read_cancellable(&self, buf: &mut [u8], token: &CancelToken) -> io::Result<usize> {
+        loop {
+            wait(self, PollFlags::IN, token)?;
+            match self.try_read(buf) {
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    if is_regular_file(self)? {
+                        return IoExt::read(self, buf);
+                    }
+                }
+                otherwise => return otherwise,
+            }
+        }
+    }
+
+    fn read_vectored_cancellable(
+        &self,
+        bufs: &mut [IoSliceMut],
+        token: &CancelToken,
+    ) -> io::Result<usize> {
+        loop {
+            wait(self, PollFlags::IN, token)?;
+            match self.try_read_vectored(bufs) {
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    if is_regular_file(self)? {
+                        return IoExt::read_vectored(self, bufs);
+                    }
+                }
+                otherwise => return otherwise,
+            }
+        }
+    }
+
+    fn write_cancellable(&self, buf: &[u8], token: &CancelToken) -> io::Result<usize> {
+        loop {
+            wait(self, PollFlags::OUT, token)?;
+            match self.try_write(buf) {
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    if is_regular_file(self)? {
+                        return IoExt::write(self, buf);
+                    }
+                }
+                otherwise => return otherwise,
+            }
+        }
+    }
+}
+
+/// Test whether `handle` is a regular file, which `poll` always reports as
+/// ready, so that waiting for it after a `WouldBlock` would spin.
+fn is_regular_file<Filelike: AsFilelike>(handle: &Filelike) -> io::Result<bool> {
+    Ok(FileType::from_raw_mode(fstat(handle.as_filelike())?.st_mode) == FileType::RegularFile)
+}
+
+/// Wait until `handle` is ready for any of the operations in `events`, or
+/// fail if `token` is cancelled first.
+fn wait<Filelike: AsFilelike>(
+    handle: &Filelike,
+    events: PollFlags,
+    token: &CancelToken,
+) -> io::Result<()> {
+    let mut fds = [
+        PollFd::from_filelike(token.inner.readable(), PollFlags::IN),
+        PollFd::from_filelike(handle, events),
+    ];
+    loop {
+        match poll(&mut fds, None) {
+            Ok(_) if !fds[0].revents().is_empty() => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Interrupted,
+                    "operation cancelled",
+                ))
+            }
+            Ok(_) => return Ok(()),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+            Err(err) => return Err(err),
+        }
+    }
+}
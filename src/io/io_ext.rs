@@ -1,7 +1,12 @@
+use crate::fs::{FileIoExt, COPY_BUF_SIZE};
+#[cfg(not(windows))]
+use crate::io::timeout::WRITE_CHUNK;
+use crate::io::{poll, BorrowedBuf, BorrowedCursor, PollFd, PollFlags};
 use io_lifetimes::{AsFilelike, AsSocketlike};
 use std::fmt::Arguments;
 use std::io::{self, IoSlice, IoSliceMut, Read, Write};
 use std::slice;
+use std::time::Duration;
 
 /// Extension trait for I/O handles that are exterior-mutable readable
 /// and writeable.
@@ -23,6 +28,23 @@ pub trait IoExt {
     /// [`std::io::Read::read_exact`]: https://doc.rust-lang.org/std/io/trait.Read.html#tymethod.read_exact
     fn read_exact(&self, buf: &mut [u8]) -> io::Result<()>;
 
+    /// Pull some bytes from this source into `cursor`, advancing it by the
+    /// number of bytes read.
+    ///
+    /// This is similar to the unstable [`std::io::Read::read_buf`], except it
+    /// takes `self` by immutable reference since the entire side effect is
+    /// I/O. The cursor's memory may be uninitialized, and implementations
+    /// which can read into uninitialized memory do so without initializing it
+    /// first. The default implementation initializes the cursor and calls
+    /// `read`.
+    ///
+    /// [`std::io::Read::read_buf`]: https://doc.rust-lang.org/std/io/trait.Read.html#method.read_buf
+    fn read_buf(&self, mut cursor: BorrowedCursor<'_>) -> io::Result<()> {
+        let n = self.read(cursor.ensure_init().init_mut())?;
+        cursor.advance(n);
+        Ok(())
+    }
+
     /// Like `read`, except that it reads into a slice of buffers.
     ///
     /// This is similar to [`std::io::Read::read_vectored`], except it takes
@@ -67,6 +89,29 @@ pub trait IoExt {
     /// [`std::io::Read::read_to_string`]: https://doc.rust-lang.org/std/io/trait.Read.html#method.read_to_string
     fn read_to_string(&self, buf: &mut String) -> io::Result<usize>;
 
+    /// Read all bytes until EOF in this source, placing them into `buf`,
+    /// unless there are more than `max` of them.
+    ///
+    /// If the source has more than `max` bytes, this fails with
+    /// [`io::ErrorKind::FileTooLarge`]. Telling that the source has more
+    /// requires reading one byte past the limit, which can't be put back, so
+    /// `buf` is left holding the first `max + 1` bytes, and no data read from
+    /// the source is lost.
+    fn read_to_end_limited(&self, buf: &mut Vec<u8>, max: usize) -> io::Result<usize> {
+        read_to_end_limited_with(buf, max, true, |cursor, _| self.read_buf(cursor))
+    }
+
+    /// Read all bytes until EOF in this source, appending them to `buf`,
+    /// unless there are more than `max` of them.
+    ///
+    /// This is to [`IoExt::read_to_end_limited`] what
+    /// [`IoExt::read_to_string`] is to [`IoExt::read_to_end`]. If the limit
+    /// is hit, the first `max + 1` bytes are left in `buf`, except for the
+    /// start of a UTF-8 sequence that they cut short, which is lost.
+    fn read_to_string_limited(&self, buf: &mut String, max: usize) -> io::Result<usize> {
+        append_to_string(buf, |vec| self.read_to_end_limited(vec, max))
+    }
+
     /// Read bytes from the current position without advancing the current
     /// position.
     ///
@@ -129,6 +174,314 @@ pub trait IoExt {
     ///
     /// [`std::io::Write::flush`]: https://doc.rust-lang.org/std/io/trait.Write.html#tymethod.flush
     fn flush(&self) -> io::Result<()>;
+
+    /// Like `read`, except that it fails with `io::ErrorKind::WouldBlock`
+    /// instead of blocking.
+    ///
+    /// Unlike setting [`FdFlags::NONBLOCK`], this doesn't change the flags
+    /// of the handle, which may be shared with other threads and processes.
+    ///
+    /// On POSIX-ish platforms, sockets use `MSG_DONTWAIT`, and on Linux,
+    /// other handles use `RWF_NOWAIT` where the kernel supports it. Where
+    /// neither applies, this checks for readiness with [`poll`] before
+    /// reading, so it may still block if another reader consumes the data
+    /// in between. On Windows, this is only supported for sockets, using
+    /// the same readiness check, and other handles fail with
+    /// `io::ErrorKind::Unsupported`.
+    ///
+    /// [`FdFlags::NONBLOCK`]: crate::fs::FdFlags::NONBLOCK
+    /// [`poll`]: crate::io::poll
+    fn try_read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let _ = buf;
+        Err(unsupported_nonblocking())
+    }
+
+    /// Like `read_vectored`, except that it fails with
+    /// `io::ErrorKind::WouldBlock` instead of blocking.
+    ///
+    /// See [`IoExt::try_read`] for details.
+    fn try_read_vectored(&self, bufs: &mut [IoSliceMut]) -> io::Result<usize> {
+        let _ = bufs;
+        Err(unsupported_nonblocking())
+    }
+
+    /// Like `write`, except that it fails with `io::ErrorKind::WouldBlock`
+    /// instead of blocking.
+    ///
+    /// Where neither `MSG_DONTWAIT` nor `RWF_NOWAIT` applies, this checks for
+    /// readiness with [`poll`] before writing, and writes at most `PIPE_BUF`
+    /// bytes. See [`IoExt::try_read`] for details.
+    ///
+    /// [`poll`]: crate::io::poll
+    fn try_write(&self, buf: &[u8]) -> io::Result<usize> {
+        let _ = buf;
+        Err(unsupported_nonblocking())
+    }
+
+    /// Write up to `len` bytes from `src`, starting at `offset`, to this
+    /// handle, and return the number of bytes written.
+    ///
+    /// Fewer than `len` bytes are written if the end of `src` is reached, or
+    /// if this handle is non-blocking and can't accept more data; in the
+    /// latter case, this fails with `io::ErrorKind::WouldBlock` only if
+    /// nothing was written. `src`'s current position is not used or
+    /// changed.
+    ///
+    /// On Linux, this uses [`sendfile`], or [`splice`] for pipes, so the
+    /// data isn't copied through userspace. Elsewhere, or if neither
+    /// applies, this falls back to copying with [`FileIoExt::read_at`] and
+    /// [`IoExt::write`].
+    ///
+    /// [`sendfile`]: https://man7.org/linux/man-pages/man2/sendfile.2.html
+    /// [`splice`]: https://man7.org/linux/man-pages/man2/splice.2.html
+    fn transfer_from<Src: AsFilelike + FileIoExt>(
+        &self,
+        src: &Src,
+        offset: u64,
+        len: u64,
+    ) -> io::Result<u64>
+    where
+        Self: Sized,
+    {
+        transfer_by_copying(self, src, offset, len)
+    }
+}
+
+/// Implement `transfer_from` by reading into a buffer and writing it out.
+fn transfer_by_copying<Dst: IoExt + ?Sized, Src: FileIoExt + ?Sized>(
+    dst: &Dst,
+    src: &Src,
+    offset: u64,
+    len: u64,
+) -> io::Result<u64> {
+    let mut buf = vec![0; len.min(COPY_BUF_SIZE) as usize];
+    let mut copied = 0;
+    while copied < len {
+        let chunk = (len - copied).min(buf.len() as u64) as usize;
+        let pos = offset
+            .checked_add(copied)
+            .ok_or_else(|| io::Error::other("offset overflow"))?;
+        let n = match src.read_at(&mut buf[..chunk], pos) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        let mut written = 0;
+        while written < n {
+            match dst.write(&buf[written..n]) {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "failed to write whole buffer",
+                    ))
+                }
+                Ok(m) => {
+                    written += m;
+                    copied += m as u64;
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+                // The rest of the data is still in `src`, so report the
+                // partial transfer.
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock && copied != 0 => {
+                    return Ok(copied)
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+    Ok(copied)
+}
+
+/// The minimum amount to grow the buffer by in `read_to_end_limited_with`.
+const MIN_GROWTH: usize = 8 * 1024;
+
+/// Read until EOF into `buf`, failing with `io::ErrorKind::FileTooLarge` if
+/// there are more than `max` bytes.
+///
+/// `read` is passed a cursor to fill, and the number of bytes read so far.
+/// If `keep_probe` is set, the byte read past the limit to tell that there
+/// are more is appended to `buf`, for sources which can't read it again.
+pub(crate) fn read_to_end_limited_with(
+    buf: &mut Vec<u8>,
+    max: usize,
+    keep_probe: bool,
+    mut read: impl FnMut(BorrowedCursor<'_>, u64) -> io::Result<()>,
+) -> io::Result<usize> {
+    let start_len = buf.len();
+
+    // The number of bytes past `buf.len()` which earlier reads initialized.
+    let mut initialized = 0;
+
+    loop {
+        let total = buf.len() - start_len;
+        if total == max {
+            // Read one more byte, to tell whether we're at EOF.
+            let mut probe = [0_u8; 1];
+            let mut probe = BorrowedBuf::from(&mut probe[..]);
+            match read(probe.unfilled(), total as u64) {
+                Ok(()) if probe.is_empty() => return Ok(total),
+                Ok(()) => {
+                    if keep_probe {
+                        buf.extend_from_slice(probe.filled());
+                    }
+                    return Err(io::Error::new(
+                        io::ErrorKind::FileTooLarge,
+                        format!("more than the limit of {} bytes to read", max),
+                    ));
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        if buf.len() == buf.capacity() {
+            // Double the amount read so far, like `Vec` does, but don't
+            // reserve more than the limit allows.
+            buf.reserve(total.max(MIN_GROWTH).min(max - total));
+            initialized = 0;
+        }
+
+        let spare = buf.spare_capacity_mut();
+        let spare_len = spare.len().min(max - total);
+        let mut read_buf = BorrowedBuf::from(&mut spare[..spare_len]);
+        // SAFETY: These bytes were initialized by earlier reads.
+        unsafe { read_buf.set_init(initialized.min(spare_len)) };
+        match read(read_buf.unfilled(), total as u64) {
+            Ok(()) => (),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+        let n = read_buf.len();
+        if n == 0 {
+            return Ok(total);
+        }
+        initialized = read_buf.init_len() - n;
+
+        // SAFETY: `BorrowedBuf` guarantees that its filled bytes are
+        // initialized.
+        unsafe { buf.set_len(buf.len() + n) };
+    }
+}
+
+/// Call `read` to append bytes to `buf`, and check that they're valid UTF-8
+/// afterwards, like `Read::read_to_string` does.
+///
+/// If `read` fails with `io::ErrorKind::FileTooLarge`, the bytes it read are
+/// kept, except for a UTF-8 sequence at the end which the limit cut short.
+pub(crate) fn append_to_string(
+    buf: &mut String,
+    read: impl FnOnce(&mut Vec<u8>) -> io::Result<usize>,
+) -> io::Result<usize> {
+    /// Truncate the `Vec` to `len` when dropped, to remove bytes which aren't
+    /// known to be UTF-8, even if we unwind.
+    struct Guard<'a> {
+        buf: &'a mut Vec<u8>,
+        len: usize,
+    }
+
+    impl Drop for Guard<'_> {
+        fn drop(&mut self) {
+            self.buf.truncate(self.len);
+        }
+    }
+
+    let len = buf.len();
+    // SAFETY: The guard removes anything we append, unless we've checked that
+    // it's valid UTF-8.
+    let mut guard = Guard {
+        buf: unsafe { buf.as_mut_vec() },
+        len,
+    };
+    let result = read(guard.buf);
+    match std::str::from_utf8(&guard.buf[guard.len..]) {
+        Ok(_) => {
+            guard.len = guard.buf.len();
+            result
+        }
+        Err(err)
+            if err.error_len().is_none()
+                && matches!(&result, Err(e) if e.kind() == io::ErrorKind::FileTooLarge) =>
+        {
+            guard.len += err.valid_up_to();
+            result
+        }
+        Err(_) => result.and_then(|_| {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "stream did not contain valid UTF-8",
+            ))
+        }),
+    }
+}
+
+/// The largest number of buffers the vectored I/O system calls accept.
+///
+/// Passing more than this fails with `EINVAL`. POSIX requires `IOV_MAX` to be
+/// at least 16, which we assume on platforms not known to allow more.
+#[cfg(any(
+    target_os = "android",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "illumos",
+    target_os = "ios",
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "solaris",
+))]
+pub(crate) const IOV_MAX: usize = 1024;
+#[cfg(not(any(
+    windows,
+    target_os = "android",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "illumos",
+    target_os = "ios",
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "solaris",
+)))]
+pub(crate) const IOV_MAX: usize = 16;
+
+fn unsupported_nonblocking() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "non-blocking I/O is not supported on this handle",
+    )
+}
+
+/// Test whether `handle` is ready for any of the operations in `events`,
+/// without blocking.
+#[cfg(not(windows))]
+fn is_ready<Filelike: AsFilelike>(handle: &Filelike, events: PollFlags) -> io::Result<bool> {
+    loop {
+        match poll(
+            &mut [PollFd::from_filelike(handle, events)],
+            Some(Duration::ZERO),
+        ) {
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+            otherwise => return otherwise.map(|n| n != 0),
+        }
+    }
+}
+
+/// Test whether `handle` is ready for any of the operations in `events`,
+/// without blocking.
+#[cfg(windows)]
+fn is_ready<Socketlike: AsSocketlike>(handle: &Socketlike, events: PollFlags) -> io::Result<bool> {
+    loop {
+        match poll(
+            &mut [PollFd::from_socketlike(handle, events)],
+            Some(Duration::ZERO),
+        ) {
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+            otherwise => return otherwise.map(|n| n != 0),
+        }
+    }
 }
 
 /// Skip any leading elements in `bufs` which are empty buffers.
@@ -214,6 +567,16 @@ impl<T: AsFilelike + AsSocketlike> IoExt for T {
         Read::read(&mut &*self.as_filelike_view::<std::fs::File>(), buf)
     }
 
+    #[inline]
+    fn read_buf(&self, mut cursor: BorrowedCursor<'_>) -> io::Result<()> {
+        // SAFETY: `read_uninit` doesn't de-initialize any bytes.
+        let (init, _) = rustix::io::read_uninit(self.as_filelike(), unsafe { cursor.as_mut() })?;
+        let n = init.len();
+        // SAFETY: `read_uninit` initialized the first `n` bytes.
+        unsafe { cursor.advance_unchecked(n) };
+        Ok(())
+    }
+
     #[inline]
     fn read_exact(&self, buf: &mut [u8]) -> io::Result<()> {
         Read::read_exact(&mut &*self.as_filelike_view::<std::fs::File>(), buf)
@@ -275,6 +638,157 @@ impl<T: AsFilelike + AsSocketlike> IoExt for T {
     fn write_fmt(&self, fmt: Arguments) -> io::Result<()> {
         Write::write_fmt(&mut &*self.as_filelike_view::<std::fs::File>(), fmt)
     }
+
+    fn try_read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        use rustix::io::Errno;
+        use rustix::net::{recv, RecvFlags};
+
+        // For sockets, use `MSG_DONTWAIT`.
+        match recv(self, buf, RecvFlags::DONTWAIT) {
+            Err(Errno::NOTSOCK) => {}
+            otherwise => return Ok(otherwise?),
+        }
+
+        // On Linux, use `preadv2` with `RWF_NOWAIT`, at the current position.
+        #[cfg(any(target_os = "android", target_os = "linux"))]
+        {
+            use rustix::io::{preadv2, ReadWriteFlags};
+
+            let mut iovs = [IoSliceMut::new(buf)];
+            match preadv2(self, &mut iovs, u64::MAX, ReadWriteFlags::NOWAIT) {
+                Err(Errno::NOSYS) | Err(Errno::NOTSUP) | Err(Errno::INVAL) => {}
+                otherwise => return Ok(otherwise?),
+            }
+        }
+
+        // Otherwise, check for readiness first.
+        if !is_ready(self, PollFlags::IN)? {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        IoExt::read(self, buf)
+    }
+
+    fn try_read_vectored(&self, bufs: &mut [IoSliceMut]) -> io::Result<usize> {
+        use rustix::io::Errno;
+
+        // Read into no more buffers than the system calls accept.
+        let len = bufs.len().min(IOV_MAX);
+        let bufs = &mut bufs[..len];
+
+        // For sockets, use `MSG_DONTWAIT`.
+        #[cfg(not(target_os = "redox"))]
+        {
+            use rustix::net::{recvmsg, RecvAncillaryBuffer, RecvFlags};
+
+            let mut control = RecvAncillaryBuffer::default();
+            match recvmsg(self, bufs, &mut control, RecvFlags::DONTWAIT) {
+                Err(Errno::NOTSOCK) => {}
+                otherwise => return Ok(otherwise?.bytes),
+            }
+        }
+
+        // On Linux, use `preadv2` with `RWF_NOWAIT`, at the current position.
+        #[cfg(any(target_os = "android", target_os = "linux"))]
+        {
+            use rustix::io::{preadv2, ReadWriteFlags};
+
+            match preadv2(self, bufs, u64::MAX, ReadWriteFlags::NOWAIT) {
+                Err(Errno::NOSYS) | Err(Errno::NOTSUP) | Err(Errno::INVAL) => {}
+                otherwise => return Ok(otherwise?),
+            }
+        }
+
+        // Otherwise, check for readiness first.
+        if !is_ready(self, PollFlags::IN)? {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        IoExt::read_vectored(self, bufs)
+    }
+
+    fn try_write(&self, buf: &[u8]) -> io::Result<usize> {
+        use rustix::io::Errno;
+        use rustix::net::{send, SendFlags};
+
+        // For sockets, use `MSG_DONTWAIT`.
+        match send(self, buf, SendFlags::DONTWAIT) {
+            Err(Errno::NOTSOCK) => {}
+            otherwise => return Ok(otherwise?),
+        }
+
+        // On Linux, use `pwritev2` with `RWF_NOWAIT`, at the current position.
+        #[cfg(any(target_os = "android", target_os = "linux"))]
+        {
+            use rustix::io::{pwritev2, ReadWriteFlags};
+
+            let iovs = [IoSlice::new(buf)];
+            match pwritev2(self, &iovs, u64::MAX, ReadWriteFlags::NOWAIT) {
+                Err(Errno::NOSYS) | Err(Errno::NOTSUP) | Err(Errno::INVAL) => {}
+                otherwise => return Ok(otherwise?),
+            }
+        }
+
+        // Otherwise, check for readiness first, and write no more than can be
+        // written without blocking.
+        if !is_ready(self, PollFlags::OUT)? {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        IoExt::write(self, &buf[..buf.len().min(WRITE_CHUNK)])
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    fn transfer_from<Src: AsFilelike + FileIoExt>(
+        &self,
+        src: &Src,
+        offset: u64,
+        len: u64,
+    ) -> io::Result<u64> {
+        use rustix::fs::sendfile;
+        use rustix::io::Errno;
+        use rustix::pipe::{splice, SpliceFlags};
+
+        let mut use_splice = false;
+        let mut copied = 0;
+        while copied < len {
+            let pos = offset
+                .checked_add(copied)
+                .ok_or_else(|| io::Error::other("offset overflow"))?;
+            let chunk = (len - copied).min(usize::MAX as u64) as usize;
+            // Pass copies of `pos`, since we track progress with `copied`,
+            // and need the original if we fall back to copying.
+            let result = if use_splice {
+                splice(
+                    src.as_filelike(),
+                    Some(&mut { pos }),
+                    self,
+                    None,
+                    chunk,
+                    SpliceFlags::empty(),
+                )
+            } else {
+                sendfile(self, src.as_filelike(), Some(&mut { pos }), chunk)
+            };
+            match result {
+                Ok(0) => break,
+                Ok(n) => copied += n as u64,
+                Err(Errno::INTR) => (),
+                Err(Errno::AGAIN) if copied != 0 => break,
+                // `sendfile` doesn't support this handle, but `splice` may,
+                // if it's a pipe.
+                Err(Errno::INVAL) | Err(Errno::NOSYS) if !use_splice => use_splice = true,
+                // Neither applies, so copy the rest in userspace.
+                Err(Errno::INVAL) | Err(Errno::NOSYS) => {
+                    return match transfer_by_copying(self, src, pos, len - copied) {
+                        Err(err) if err.kind() == io::ErrorKind::WouldBlock && copied != 0 => {
+                            Ok(copied)
+                        }
+                        otherwise => Ok(copied + otherwise?),
+                    };
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(copied)
+    }
 }
 
 #[cfg(windows)]
@@ -534,6 +1048,27 @@ impl IoExt for std::net::TcpStream {
     fn write_fmt(&self, fmt: Arguments) -> io::Result<()> {
         Write::write_fmt(&mut &*self.as_socketlike_view::<std::net::TcpStream>(), fmt)
     }
+
+    fn try_read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        if !is_ready(self, PollFlags::IN)? {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        IoExt::read(self, buf)
+    }
+
+    fn try_read_vectored(&self, bufs: &mut [IoSliceMut]) -> io::Result<usize> {
+        if !is_ready(self, PollFlags::IN)? {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        IoExt::read_vectored(self, bufs)
+    }
+
+    fn try_write(&self, buf: &[u8]) -> io::Result<usize> {
+        if !is_ready(self, PollFlags::OUT)? {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        IoExt::write(self, buf)
+    }
 }
 
 #[cfg(windows)]
@@ -600,6 +1135,27 @@ impl IoExt for cap_std::net::TcpStream {
         self.as_socketlike_view::<std::net::TcpStream>()
             .write_fmt(fmt)
     }
+
+    fn try_read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        if !is_ready(self, PollFlags::IN)? {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        IoExt::read(self, buf)
+    }
+
+    fn try_read_vectored(&self, bufs: &mut [IoSliceMut]) -> io::Result<usize> {
+        if !is_ready(self, PollFlags::IN)? {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        IoExt::read_vectored(self, bufs)
+    }
+
+    fn try_write(&self, buf: &[u8]) -> io::Result<usize> {
+        if !is_ready(self, PollFlags::OUT)? {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        IoExt::write(self, buf)
+    }
 }
 
 fn _io_ext_can_be_trait_object(_: &dyn IoExt) {}
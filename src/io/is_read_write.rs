@@ -83,6 +83,15 @@ impl IsReadWrite for cap_async_std::fs_utf8::File {
     }
 }
 
+#[cfg(all(windows, feature = "tokio_impls"))]
+impl IsReadWrite for tokio::fs::File {
+    #[inline]
+    fn is_read_write(&self) -> io::Result<(bool, bool)> {
+        use io_lifetimes::AsFilelike;
+        file_is_read_write(&self.as_filelike_view::<std::fs::File>())
+    }
+}
+
 #[cfg(windows)]
 impl IsReadWrite for std::net::TcpStream {
     #[inline]
@@ -115,6 +124,14 @@ impl IsReadWrite for cap_async_std::net::TcpStream {
     }
 }
 
+#[cfg(all(windows, feature = "tokio_impls"))]
+impl IsReadWrite for tokio::net::TcpStream {
+    #[inline]
+    fn is_read_write(&self) -> io::Result<(bool, bool)> {
+        raw_socket_is_read_write(self.as_raw_socket())
+    }
+}
+
 #[cfg(windows)]
 #[inline]
 fn file_is_read_write(file: &std::fs::File) -> std::io::Result<(bool, bool)> {
@@ -1,11 +1,46 @@
 //! I/O extension traits.
 
+#[cfg(any(feature = "cap_async_std_impls", feature = "tokio_impls"))]
+mod async_peek;
+#[cfg(any(feature = "cap_async_std_impls", feature = "tokio_impls"))]
+mod async_read_ready;
+mod borrowed_buf;
+#[cfg(not(windows))]
+mod cancel;
 mod io_ext;
 mod is_read_write;
 mod peek;
+mod poll;
+#[cfg(any(target_os = "android", target_os = "linux"))]
+mod poller;
+#[cfg(all(
+    any(target_os = "android", target_os = "linux"),
+    feature = "use_futures_io"
+))]
+mod reactor;
 mod read_ready;
+mod timeout;
 
+#[cfg(any(feature = "cap_async_std_impls", feature = "tokio_impls"))]
+pub use async_peek::AsyncPeek;
+#[cfg(any(feature = "cap_async_std_impls", feature = "tokio_impls"))]
+pub use async_read_ready::AsyncReadReady;
+pub use borrowed_buf::{BorrowedBuf, BorrowedCursor};
+#[cfg(not(windows))]
+pub use cancel::{CancelToken, Canceller, IoCancelExt};
 pub use io_ext::IoExt;
+#[cfg(not(windows))]
+pub(crate) use io_ext::IOV_MAX;
+pub(crate) use io_ext::{append_to_string, read_to_end_limited_with};
 pub use is_read_write::IsReadWrite;
 pub use peek::{peek_from_bufread, Peek};
+pub use poll::{poll, PollFd, PollFlags};
+#[cfg(any(target_os = "android", target_os = "linux"))]
+pub use poller::{Event, Events, Poller, Trigger, Waker};
+#[cfg(all(
+    any(target_os = "android", target_os = "linux"),
+    feature = "use_futures_io"
+))]
+pub use reactor::Async;
 pub use read_ready::ReadReady;
+pub use timeout::{IoTimeoutExt, TimedOut};
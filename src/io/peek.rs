@@ -1,6 +1,6 @@
 use std::fs::File;
 use std::io::{
-    self, BufRead, BufReader, Chain, Cursor, Empty, Read, Repeat, Seek, SeekFrom, StdinLock, Take,
+    self, BufRead, BufReader, Chain, Cursor, Empty, Read, Repeat, Seek, StdinLock, Take,
 };
 use std::net::TcpStream;
 #[cfg(unix)]
@@ -28,7 +28,7 @@ pub trait Peek {
 impl Peek for File {
     #[inline]
     fn peek(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let pos = self.seek(SeekFrom::Current(0))?;
+        let pos = self.stream_position()?;
         crate::fs::FileIoExt::read_at(self, buf, pos)
     }
 }
@@ -37,7 +37,7 @@ impl Peek for File {
 impl Peek for cap_std::fs::File {
     #[inline]
     fn peek(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let pos = self.seek(SeekFrom::Current(0))?;
+        let pos = self.stream_position()?;
         crate::fs::FileIoExt::read_at(self, buf, pos)
     }
 }
@@ -46,7 +46,7 @@ impl Peek for cap_std::fs::File {
 impl Peek for cap_std::fs_utf8::File {
     #[inline]
     fn peek(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let pos = self.seek(SeekFrom::Current(0))?;
+        let pos = self.stream_position()?;
         crate::fs::FileIoExt::read_at(self, buf, pos)
     }
 }
@@ -100,7 +100,7 @@ impl Peek for Empty {
     }
 }
 
-impl<'a> Peek for &'a [u8] {
+impl Peek for &[u8] {
     #[inline]
     fn peek(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         peek_from_bufread(self, buf)
@@ -121,7 +121,7 @@ impl<B: BufRead + ?Sized> Peek for Box<B> {
     }
 }
 
-impl<'a, B: BufRead + ?Sized> Peek for &'a mut B {
+impl<B: BufRead + ?Sized> Peek for &mut B {
     #[inline]
     fn peek(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         peek_from_bufread(self, buf)
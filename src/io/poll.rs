@@ -0,0 +1,241 @@
+//! The `poll` function, and related types.
+
+use bitflags::bitflags;
+use std::io;
+use std::time::Duration;
+#[cfg(not(windows))]
+use {
+    io_lifetimes::{AsFilelike, AsSocketlike},
+    rustix::event,
+};
+#[cfg(windows)]
+use {
+    io_lifetimes::{AsSocketlike, BorrowedSocket},
+    std::marker::PhantomData,
+    std::os::windows::io::AsRawSocket,
+    windows_sys::Win32::Networking::WinSock::{
+        WSAPoll, POLLERR, POLLHUP, POLLNVAL, POLLRDNORM, POLLWRNORM, SOCKET, SOCKET_ERROR,
+        WSAPOLLFD,
+    },
+};
+
+bitflags! {
+    /// Interest and readiness flags for use with [`PollFd`] and [`poll`].
+    #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+    pub struct PollFlags: u16 {
+        /// The handle is readable, or has reached the end of the stream.
+        const IN = 0x01;
+
+        /// The handle is writable.
+        const OUT = 0x02;
+
+        /// An error is pending on the handle.
+        ///
+        /// This is only reported in [`PollFd::revents`], and need not be
+        /// requested.
+        const ERR = 0x04;
+
+        /// The peer hung up.
+        ///
+        /// This is only reported in [`PollFd::revents`], and need not be
+        /// requested.
+        const HUP = 0x08;
+
+        /// The handle is not open.
+        ///
+        /// This is only reported in [`PollFd::revents`], and need not be
+        /// requested.
+        const NVAL = 0x10;
+    }
+}
+
+/// A handle to wait on, together with the interest and readiness flags for
+/// use with [`poll`].
+#[cfg(not(windows))]
+#[repr(transparent)]
+pub struct PollFd<'fd> {
+    inner: event::PollFd<'fd>,
+}
+
+/// A handle to wait on, together with the interest and readiness flags for
+/// use with [`poll`].
+#[cfg(windows)]
+#[repr(transparent)]
+pub struct PollFd<'fd> {
+    inner: WSAPOLLFD,
+    _phantom: PhantomData<BorrowedSocket<'fd>>,
+}
+
+#[cfg(not(windows))]
+impl<'fd> PollFd<'fd> {
+    /// Construct a new `PollFd` for a file-like handle, such as a file, a
+    /// pipe, a character device, or `Stdin`.
+    ///
+    /// This is not available on Windows, where only sockets can be polled.
+    #[inline]
+    pub fn from_filelike<Filelike: AsFilelike>(filelike: &'fd Filelike, events: PollFlags) -> Self {
+        Self {
+            inner: event::PollFd::from_borrowed_fd(filelike.as_filelike(), to_rustix(events)),
+        }
+    }
+
+    /// Construct a new `PollFd` for a socket-like handle, such as a
+    /// `TcpStream` or a `UnixStream`.
+    #[inline]
+    pub fn from_socketlike<Socketlike: AsSocketlike>(
+        socketlike: &'fd Socketlike,
+        events: PollFlags,
+    ) -> Self {
+        Self {
+            inner: event::PollFd::from_borrowed_fd(socketlike.as_socketlike(), to_rustix(events)),
+        }
+    }
+
+    /// Return the readiness flags set by the most recent call to [`poll`].
+    #[inline]
+    pub fn revents(&self) -> PollFlags {
+        from_rustix(self.inner.revents())
+    }
+
+    /// Clear the readiness flags.
+    #[inline]
+    pub fn clear_revents(&mut self) {
+        self.inner.clear_revents()
+    }
+}
+
+#[cfg(windows)]
+impl<'fd> PollFd<'fd> {
+    /// Construct a new `PollFd` for a socket-like handle, such as a
+    /// `TcpStream`.
+    #[inline]
+    pub fn from_socketlike<Socketlike: AsSocketlike>(
+        socketlike: &'fd Socketlike,
+        events: PollFlags,
+    ) -> Self {
+        Self {
+            inner: WSAPOLLFD {
+                fd: socketlike.as_socketlike().as_raw_socket() as SOCKET,
+                events: to_wsa(events),
+                revents: 0,
+            },
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Return the readiness flags set by the most recent call to [`poll`].
+    #[inline]
+    pub fn revents(&self) -> PollFlags {
+        from_wsa(self.inner.revents)
+    }
+
+    /// Clear the readiness flags.
+    #[inline]
+    pub fn clear_revents(&mut self) {
+        self.inner.revents = 0;
+    }
+}
+
+/// Wait for any of the handles in `fds` to become ready.
+///
+/// On return, [`PollFd::revents`] reports the readiness of each handle, and
+/// the returned value is the number of handles with non-empty readiness. A
+/// `timeout` of `None` waits indefinitely; otherwise `Ok(0)` indicates that
+/// the timeout expired. Timeouts are rounded up to the next millisecond.
+///
+/// Like the underlying system call, this may fail with
+/// `io::ErrorKind::Interrupted`, in which case it may simply be retried.
+///
+/// This is similar to [`poll`] on POSIX-ish platforms and [`WSAPoll`] on
+/// Windows.
+///
+/// [`poll`]: https://pubs.opengroup.org/onlinepubs/9699919799/functions/poll.html
+/// [`WSAPoll`]: https://docs.microsoft.com/en-us/windows/win32/api/winsock2/nf-winsock2-wsapoll
+pub fn poll(fds: &mut [PollFd<'_>], timeout: Option<Duration>) -> io::Result<usize> {
+    let timeout = timeout_millis(timeout);
+
+    #[cfg(not(windows))]
+    {
+        // SAFETY: `PollFd` is a `repr(transparent)` wrapper around
+        // `rustix::event::PollFd`.
+        let fds = unsafe { &mut *(fds as *mut [PollFd<'_>] as *mut [event::PollFd<'_>]) };
+        Ok(event::poll(fds, timeout)?)
+    }
+
+    #[cfg(windows)]
+    {
+        let len = fds
+            .len()
+            .try_into()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many handles to poll"))?;
+        // SAFETY: `PollFd` is a `repr(transparent)` wrapper around
+        // `WSAPOLLFD`.
+        let n = unsafe { WSAPoll(fds.as_mut_ptr().cast::<WSAPOLLFD>(), len, timeout) };
+        if n == SOCKET_ERROR {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(n as usize)
+    }
+}
+
+/// Convert an optional timeout into the milliseconds form used by `poll`
+/// and similar system calls, where -1 means to wait indefinitely.
+pub(crate) fn timeout_millis(timeout: Option<Duration>) -> i32 {
+    match timeout {
+        None => -1,
+        Some(timeout) => {
+            // Round up, so that a small non-zero timeout doesn't busy-loop.
+            let millis = timeout
+                .as_millis()
+                .saturating_add(u128::from(timeout.subsec_nanos() % 1_000_000 != 0));
+            millis.try_into().unwrap_or(i32::MAX)
+        }
+    }
+}
+
+#[cfg(not(windows))]
+fn to_rustix(flags: PollFlags) -> event::PollFlags {
+    let mut result = event::PollFlags::empty();
+    result.set(event::PollFlags::IN, flags.contains(PollFlags::IN));
+    result.set(event::PollFlags::OUT, flags.contains(PollFlags::OUT));
+    result.set(event::PollFlags::ERR, flags.contains(PollFlags::ERR));
+    result.set(event::PollFlags::HUP, flags.contains(PollFlags::HUP));
+    result.set(event::PollFlags::NVAL, flags.contains(PollFlags::NVAL));
+    result
+}
+
+#[cfg(not(windows))]
+fn from_rustix(flags: event::PollFlags) -> PollFlags {
+    let mut result = PollFlags::empty();
+    result.set(PollFlags::IN, flags.contains(event::PollFlags::IN));
+    result.set(PollFlags::OUT, flags.contains(event::PollFlags::OUT));
+    result.set(PollFlags::ERR, flags.contains(event::PollFlags::ERR));
+    result.set(PollFlags::HUP, flags.contains(event::PollFlags::HUP));
+    result.set(PollFlags::NVAL, flags.contains(event::PollFlags::NVAL));
+    result
+}
+
+#[cfg(windows)]
+fn to_wsa(flags: PollFlags) -> i16 {
+    // `WSAPoll` fails with `WSAEINVAL` if any output-only flags are
+    // requested, so only translate the interest flags.
+    let mut result: i16 = 0;
+    if flags.contains(PollFlags::IN) {
+        result |= POLLRDNORM;
+    }
+    if flags.contains(PollFlags::OUT) {
+        result |= POLLWRNORM;
+    }
+    result
+}
+
+#[cfg(windows)]
+fn from_wsa(flags: i16) -> PollFlags {
+    let mut result = PollFlags::empty();
+    result.set(PollFlags::IN, flags & POLLRDNORM != 0);
+    result.set(PollFlags::OUT, flags & POLLWRNORM != 0);
+    result.set(PollFlags::ERR, flags & POLLERR != 0);
+    result.set(PollFlags::HUP, flags & POLLHUP != 0);
+    result.set(PollFlags::NVAL, flags & POLLNVAL != 0);
+    result
+}
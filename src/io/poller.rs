@@ -0,0 +1,340 @@
+//! The `Poller` type, and related types.
+
+use crate::io::poll::timeout_millis;
+use crate::io::PollFlags;
+use io_lifetimes::raw::AsRawFilelike;
+use io_lifetimes::AsFilelike;
+use rustix::event::{epoll, eventfd, EventfdFlags};
+use rustix::fd::{OwnedFd, RawFd};
+use rustix::io::{read, write, Errno};
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The token reserved for the `Poller`'s internal wake-up event.
+const WAKER_TOKEN: u64 = u64::MAX;
+
+/// How a handle registered with a [`Poller`] reports readiness.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Trigger {
+    /// Readiness is reported by every [`Poller::wait`] for as long as the
+    /// handle remains ready.
+    Level,
+
+    /// Readiness is reported once each time the handle becomes ready, and
+    /// not again until it has been drained and becomes ready again.
+    Edge,
+}
+
+/// A readiness event reported by [`Poller::wait`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Event {
+    token: u64,
+    readiness: PollFlags,
+}
+
+impl Event {
+    /// Return the token the handle was registered with.
+    #[inline]
+    pub fn token(&self) -> u64 {
+        self.token
+    }
+
+    /// Return the readiness of the handle.
+    #[inline]
+    pub fn readiness(&self) -> PollFlags {
+        self.readiness
+    }
+}
+
+/// A buffer of events, filled in by [`Poller::wait`].
+pub struct Events {
+    raw: epoll::EventVec,
+    ready: Vec<Event>,
+}
+
+impl Events {
+    /// Create a new buffer which can receive up to `capacity` events from
+    /// each call to [`Poller::wait`].
+    pub fn with_capacity(capacity: usize) -> Self {
+        // Reserve one extra slot for the wake-up event.
+        let capacity = capacity.saturating_add(1);
+        Self {
+            raw: epoll::EventVec::with_capacity(capacity),
+            ready: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Iterate over the events reported by the most recent wait.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = Event> + '_ {
+        self.ready.iter().copied()
+    }
+
+    /// Return the number of events reported by the most recent wait.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.ready.len()
+    }
+
+    /// Test whether the most recent wait reported no events.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.ready.is_empty()
+    }
+}
+
+/// A handle which can wake up a [`Poller`] from another thread.
+#[derive(Clone)]
+pub struct Waker {
+    eventfd: Arc<OwnedFd>,
+}
+
+impl Waker {
+    /// Wake up the current or next call to [`Poller::wait`].
+    ///
+    /// Multiple wake-ups before a wait are coalesced into one.
+    pub fn wake(&self) -> io::Result<()> {
+        match write(&*self.eventfd, &1_u64.to_ne_bytes()) {
+            // The counter is saturated, so a wake-up is already pending.
+            Err(Errno::AGAIN) => Ok(()),
+            otherwise => otherwise.map(|_| ()).map_err(Into::into),
+        }
+    }
+}
+
+/// The state of a handle which can't be registered with epoll, such as a
+/// regular file, and which is therefore always ready.
+struct AlwaysReady {
+    token: u64,
+    interest: PollFlags,
+    trigger: Trigger,
+    pending: bool,
+}
+
+/// A persistent registry of handles to wait on.
+///
+/// Unlike [`poll`], which scans every handle on every call, handles are
+/// registered once with a token, and [`Poller::wait`] reports the tokens of
+/// the handles that are ready. This works with any file-like or socket-like
+/// handle, including files, pipes, sockets, character devices, and `Stdin`.
+///
+/// Regular files are always ready for reading and writing, and the kernel
+/// refuses to register them, so they're tracked separately and reported as
+/// ready on every wait, or once per registration if [`Trigger::Edge`] is
+/// used.
+///
+/// Token `u64::MAX` is reserved for internal use.
+///
+/// This is currently only available on Linux and Android, where it is
+/// implemented with [`epoll`].
+///
+/// [`poll`]: crate::io::poll
+/// [`epoll`]: https://man7.org/linux/man-pages/man7/epoll.7.html
+pub struct Poller {
+    epoll: OwnedFd,
+    waker: Arc<OwnedFd>,
+    always_ready: Mutex<HashMap<RawFd, AlwaysReady>>,
+}
+
+impl Poller {
+    /// Create a new, empty, `Poller`.
+    pub fn new() -> io::Result<Self> {
+        let epoll = epoll::create(epoll::CreateFlags::CLOEXEC)?;
+        let waker = eventfd(0, EventfdFlags::CLOEXEC | EventfdFlags::NONBLOCK)?;
+        epoll::add(
+            &epoll,
+            &waker,
+            epoll::EventData::new_u64(WAKER_TOKEN),
+            epoll::EventFlags::IN,
+        )?;
+        Ok(Self {
+            epoll,
+            waker: Arc::new(waker),
+            always_ready: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Register `handle` to be reported with `token` when it's ready for any
+    /// of the operations in `interest`.
+    ///
+    /// The handle must be deleted with [`Poller::delete`] before it is
+    /// closed.
+    pub fn add<Filelike: AsFilelike>(
+        &self,
+        handle: &Filelike,
+        token: u64,
+        interest: PollFlags,
+        trigger: Trigger,
+    ) -> io::Result<()> {
+        check_token(token)?;
+        let fd = handle.as_filelike();
+        match epoll::add(
+            &self.epoll,
+            fd,
+            epoll::EventData::new_u64(token),
+            to_epoll(interest, trigger),
+        ) {
+            Err(Errno::PERM) => {
+                let mut always_ready = self.always_ready.lock().unwrap();
+                if always_ready.contains_key(&fd.as_raw_filelike()) {
+                    return Err(Errno::EXIST.into());
+                }
+                always_ready.insert(
+                    fd.as_raw_filelike(),
+                    AlwaysReady {
+                        token,
+                        interest,
+                        trigger,
+                        pending: true,
+                    },
+                );
+                Ok(())
+            }
+            otherwise => Ok(otherwise?),
+        }
+    }
+
+    /// Change the token, interest, and trigger of a handle previously
+    /// registered with [`Poller::add`].
+    pub fn modify<Filelike: AsFilelike>(
+        &self,
+        handle: &Filelike,
+        token: u64,
+        interest: PollFlags,
+        trigger: Trigger,
+    ) -> io::Result<()> {
+        check_token(token)?;
+        let fd = handle.as_filelike();
+        if let Some(entry) = self
+            .always_ready
+            .lock()
+            .unwrap()
+            .get_mut(&fd.as_raw_filelike())
+        {
+            *entry = AlwaysReady {
+                token,
+                interest,
+                trigger,
+                pending: true,
+            };
+            return Ok(());
+        }
+        Ok(epoll::modify(
+            &self.epoll,
+            fd,
+            epoll::EventData::new_u64(token),
+            to_epoll(interest, trigger),
+        )?)
+    }
+
+    /// Unregister a handle previously registered with [`Poller::add`].
+    pub fn delete<Filelike: AsFilelike>(&self, handle: &Filelike) -> io::Result<()> {
+        let fd = handle.as_filelike();
+        if self
+            .always_ready
+            .lock()
+            .unwrap()
+            .remove(&fd.as_raw_filelike())
+            .is_some()
+        {
+            return Ok(());
+        }
+        Ok(epoll::delete(&self.epoll, fd)?)
+    }
+
+    /// Wait for any of the registered handles to become ready, and fill
+    /// `events` with their tokens and readiness.
+    ///
+    /// A `timeout` of `None` waits indefinitely. This returns the number of
+    /// events, which may be zero if the timeout expired or if the wait was
+    /// woken up by a [`Waker`].
+    ///
+    /// Like the underlying system call, this may fail with
+    /// `io::ErrorKind::Interrupted`, in which case it may simply be retried.
+    pub fn wait(&self, events: &mut Events, timeout: Option<Duration>) -> io::Result<usize> {
+        events.ready.clear();
+
+        let any_pending = self
+            .always_ready
+            .lock()
+            .unwrap()
+            .values()
+            .any(|entry| entry.pending);
+        let timeout = if any_pending {
+            0
+        } else {
+            timeout_millis(timeout)
+        };
+
+        epoll::wait(&self.epoll, &mut events.raw, timeout)?;
+        for event in events.raw.iter() {
+            let token = event.data.u64();
+            if token == WAKER_TOKEN {
+                self.drain_waker()?;
+                continue;
+            }
+            events.ready.push(Event {
+                token,
+                readiness: from_epoll(event.flags),
+            });
+        }
+
+        for entry in self.always_ready.lock().unwrap().values_mut() {
+            if entry.pending && events.ready.len() < events.raw.capacity() {
+                events.ready.push(Event {
+                    token: entry.token,
+                    readiness: entry.interest & (PollFlags::IN | PollFlags::OUT),
+                });
+                entry.pending = entry.trigger == Trigger::Level;
+            }
+        }
+
+        Ok(events.ready.len())
+    }
+
+    /// Return a [`Waker`] which can be used to wake up this `Poller` from
+    /// another thread.
+    #[inline]
+    pub fn waker(&self) -> Waker {
+        Waker {
+            eventfd: Arc::clone(&self.waker),
+        }
+    }
+
+    fn drain_waker(&self) -> io::Result<()> {
+        let mut buf = [0_u8; 8];
+        match read(&*self.waker, &mut buf) {
+            Err(Errno::AGAIN) => Ok(()),
+            otherwise => otherwise.map(|_| ()).map_err(Into::into),
+        }
+    }
+}
+
+fn check_token(token: u64) -> io::Result<()> {
+    if token == WAKER_TOKEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "token u64::MAX is reserved",
+        ));
+    }
+    Ok(())
+}
+
+fn to_epoll(interest: PollFlags, trigger: Trigger) -> epoll::EventFlags {
+    let mut result = epoll::EventFlags::empty();
+    result.set(epoll::EventFlags::IN, interest.contains(PollFlags::IN));
+    result.set(epoll::EventFlags::OUT, interest.contains(PollFlags::OUT));
+    result.set(epoll::EventFlags::ET, trigger == Trigger::Edge);
+    result
+}
+
+fn from_epoll(flags: epoll::EventFlags) -> PollFlags {
+    let mut result = PollFlags::empty();
+    result.set(PollFlags::IN, flags.contains(epoll::EventFlags::IN));
+    result.set(PollFlags::OUT, flags.contains(epoll::EventFlags::OUT));
+    result.set(PollFlags::ERR, flags.contains(epoll::EventFlags::ERR));
+    result.set(PollFlags::HUP, flags.contains(epoll::EventFlags::HUP));
+    result
+}
@@ -0,0 +1,407 @@
+//! The `Async` type, and related utilities.
+
+use crate::io::{poll, Events, IoExt, PollFd, PollFlags, Poller, ReadReady, Trigger};
+use futures_io::{AsyncRead, AsyncWrite};
+use io_lifetimes::AsFilelike;
+use rustix::fs::{fstat, FileType};
+use std::collections::HashMap;
+use std::future::poll_fn;
+use std::io::{self, IoSliceMut};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+/// The global reactor, which waits for registered handles to become ready
+/// on a background thread, and wakes up the tasks waiting on them.
+struct Reactor {
+    poller: Poller,
+    sources: Mutex<HashMap<u64, Arc<Source>>>,
+    next_token: AtomicU64,
+    /// The error which stopped the reactor, if it has stopped.
+    failure: OnceLock<io::Error>,
+}
+
+/// The readiness state of a handle registered with the [`Reactor`].
+#[derive(Default)]
+struct Source {
+    read: Mutex<Direction>,
+    write: Mutex<Direction>,
+}
+
+/// The readiness state of one direction of a [`Source`].
+#[derive(Default)]
+struct Direction {
+    /// Incremented each time the handle is reported ready, so that a task
+    /// which saw `WouldBlock` can tell if it raced with a readiness event.
+    tick: u64,
+    wakers: Vec<Waker>,
+}
+
+impl Direction {
+    fn wake(&mut self) {
+        self.tick = self.tick.wrapping_add(1);
+        for waker in self.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+static REACTOR: OnceLock<Reactor> = OnceLock::new();
+/// Whether the reactor thread has been started.
+static STARTED: Mutex<bool> = Mutex::new(false);
+
+impl Reactor {
+    /// Return the global reactor, starting it if needed.
+    fn get() -> io::Result<&'static Self> {
+        let reactor = match REACTOR.get() {
+            Some(reactor) => reactor,
+            None => {
+                let poller = Poller::new()?;
+                REACTOR.get_or_init(|| Self {
+                    poller,
+                    sources: Mutex::new(HashMap::new()),
+                    next_token: AtomicU64::new(0),
+                    failure: OnceLock::new(),
+                })
+            }
+        };
+        let mut started = STARTED.lock().unwrap();
+        if !*started {
+            std::thread::Builder::new()
+                .name("system-interface-reactor".to_owned())
+                .spawn(move || reactor.run())?;
+            *started = true;
+        }
+        drop(started);
+        reactor.check()?;
+        Ok(reactor)
+    }
+
+    /// Fail with the error which stopped the reactor, if it has stopped.
+    fn check(&self) -> io::Result<()> {
+        match self.failure.get() {
+            None => Ok(()),
+            Some(err) => Err(match err.raw_os_error() {
+                Some(errno) => io::Error::from_raw_os_error(errno),
+                None => io::Error::new(err.kind(), err.to_string()),
+            }),
+        }
+    }
+
+    fn run(&self) {
+        let mut events = Events::with_capacity(64);
+        loop {
+            match self.poller.wait(&mut events, None) {
+                Ok(_) => (),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return self.fail(err),
+            }
+            let sources = self.sources.lock().unwrap();
+            for event in events.iter() {
+                if let Some(source) = sources.get(&event.token()) {
+                    let readiness = event.readiness();
+                    if readiness.intersects(PollFlags::IN | PollFlags::ERR | PollFlags::HUP) {
+                        source.read.lock().unwrap().wake();
+                    }
+                    if readiness.intersects(PollFlags::OUT | PollFlags::ERR | PollFlags::HUP) {
+                        source.write.lock().unwrap().wake();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Record that the reactor has stopped because of `err`, and wake all
+    /// the tasks waiting on it, so that they see the error rather than
+    /// waiting forever.
+    fn fail(&self, err: io::Error) {
+        let _ = self.failure.set(err);
+        for source in self.sources.lock().unwrap().values() {
+            source.read.lock().unwrap().wake();
+            source.write.lock().unwrap().wake();
+        }
+    }
+}
+
+/// An adapter which makes an I/O handle usable from async code on any
+/// executor.
+///
+/// The handle is registered with a global reactor, which waits for
+/// readiness on a background thread using a [`Poller`]. Reads and writes use
+/// [`IoExt::try_read`] and [`IoExt::try_write`], so the handle's own flags
+/// aren't changed, and it's suitable for pipes, ttys, character devices,
+/// sockets, and eventfds.
+///
+/// Regular files are always ready, so they aren't registered, and reads and
+/// writes on them are performed directly.
+///
+/// If the reactor fails, pending and future operations on registered handles
+/// fail with its error, and so does registering new handles.
+///
+/// This is currently only available on Linux and Android.
+pub struct Async<T: AsFilelike> {
+    io: Option<T>,
+    source: Option<(u64, Arc<Source>)>,
+}
+
+impl<T: AsFilelike> Async<T> {
+    /// Register `io` with the reactor.
+    pub fn new(io: T) -> io::Result<Self> {
+        if FileType::from_raw_mode(fstat(io.as_filelike())?.st_mode) == FileType::RegularFile {
+            return Ok(Self {
+                io: Some(io),
+                source: None,
+            });
+        }
+
+        let reactor = Reactor::get()?;
+        let token = reactor.next_token.fetch_add(1, Ordering::Relaxed);
+        let source = Arc::new(Source::default());
+        reactor
+            .sources
+            .lock()
+            .unwrap()
+            .insert(token, Arc::clone(&source));
+        if let Err(err) =
+            reactor
+                .poller
+                .add(&io, token, PollFlags::IN | PollFlags::OUT, Trigger::Edge)
+        {
+            reactor.sources.lock().unwrap().remove(&token);
+            return Err(err);
+        }
+
+        Ok(Self {
+            io: Some(io),
+            source: Some((token, source)),
+        })
+    }
+
+    /// Return a reference to the inner handle.
+    #[inline]
+    pub fn get_ref(&self) -> &T {
+        self.io.as_ref().unwrap()
+    }
+
+    /// Unregister the handle from the reactor and return it.
+    pub fn into_inner(mut self) -> io::Result<T> {
+        self.deregister()?;
+        Ok(self.io.take().unwrap())
+    }
+
+    /// Wait until the handle is readable, or has reached the end of the
+    /// stream.
+    ///
+    /// Once it's ready, [`ReadReady::num_ready_bytes`] reports how many bytes
+    /// can be read.
+    pub async fn readable(&self) -> io::Result<()> {
+        poll_fn(|cx| self.poll_ready(cx, PollFlags::IN)).await
+    }
+
+    /// Wait until the handle is writable.
+    pub async fn writable(&self) -> io::Result<()> {
+        poll_fn(|cx| self.poll_ready(cx, PollFlags::OUT)).await
+    }
+
+    fn poll_ready(&self, cx: &mut Context<'_>, events: PollFlags) -> Poll<io::Result<()>> {
+        self.poll_io(cx, events, |io| {
+            if is_ready(io, events)? {
+                Ok(())
+            } else {
+                Err(io::ErrorKind::WouldBlock.into())
+            }
+        })
+    }
+
+    /// Repeatedly try `op` until it doesn't fail with `WouldBlock`, or until
+    /// there have been no readiness events since the last attempt.
+    fn poll_io<R>(
+        &self,
+        cx: &mut Context<'_>,
+        events: PollFlags,
+        mut op: impl FnMut(&T) -> io::Result<R>,
+    ) -> Poll<io::Result<R>> {
+        let io = self.get_ref();
+        let (_, source) = match &self.source {
+            Some(source) => source,
+            None => return Poll::Ready(op(io)),
+        };
+        let direction = if events.contains(PollFlags::IN) {
+            &source.read
+        } else {
+            &source.write
+        };
+        loop {
+            let tick = direction.lock().unwrap().tick;
+            match op(io) {
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => (),
+                otherwise => return Poll::Ready(otherwise),
+            }
+            let mut direction = direction.lock().unwrap();
+            // Check for failure while holding the lock, so that we either
+            // see it or are woken when the reactor records it.
+            if let Err(err) = REACTOR.get().unwrap().check() {
+                return Poll::Ready(Err(err));
+            }
+            if direction.tick == tick {
+                if !direction.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                    direction.wakers.push(cx.waker().clone());
+                }
+                return Poll::Pending;
+            }
+        }
+    }
+
+    fn deregister(&mut self) -> io::Result<()> {
+        if let Some((token, _)) = self.source.take() {
+            let reactor = REACTOR.get().unwrap();
+            reactor.sources.lock().unwrap().remove(&token);
+            reactor.poller.delete(self.io.as_ref().unwrap())?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: AsFilelike + IoExt> Async<T> {
+    /// Reads data without consuming it, waiting until at least one byte is
+    /// available.
+    ///
+    /// This uses [`IoExt::peek`], so it returns `Ok(0)` for handles which
+    /// don't support peeking.
+    pub async fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        poll_fn(|cx| {
+            self.poll_io(cx, PollFlags::IN, |io| {
+                if !is_ready(io, PollFlags::IN)? {
+                    return Err(io::ErrorKind::WouldBlock.into());
+                }
+                io.peek(buf)
+            })
+        })
+        .await
+    }
+
+    fn poll_read_impl(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        if self.source.is_none() {
+            return Poll::Ready(self.get_ref().read(buf));
+        }
+        self.poll_io(cx, PollFlags::IN, |io| io.try_read(buf))
+    }
+
+    fn poll_read_vectored_impl(
+        &self,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        if self.source.is_none() {
+            return Poll::Ready(self.get_ref().read_vectored(bufs));
+        }
+        self.poll_io(cx, PollFlags::IN, |io| io.try_read_vectored(bufs))
+    }
+
+    fn poll_write_impl(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        if self.source.is_none() {
+            return Poll::Ready(self.get_ref().write(buf));
+        }
+        self.poll_io(cx, PollFlags::OUT, |io| io.try_write(buf))
+    }
+}
+
+impl<T: AsFilelike> Drop for Async<T> {
+    fn drop(&mut self) {
+        // Errors are ignored, since the handle is about to be closed anyway.
+        let _ = self.deregister();
+    }
+}
+
+impl<T: AsFilelike + ReadReady> ReadReady for Async<T> {
+    #[inline]
+    fn num_ready_bytes(&self) -> io::Result<u64> {
+        self.get_ref().num_ready_bytes()
+    }
+}
+
+impl<T: AsFilelike + IoExt> AsyncRead for Async<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_read_impl(cx, buf)
+    }
+
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_read_vectored_impl(cx, bufs)
+    }
+}
+
+impl<T: AsFilelike + IoExt> AsyncRead for &Async<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_read_impl(cx, buf)
+    }
+
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_read_vectored_impl(cx, bufs)
+    }
+}
+
+impl<T: AsFilelike + IoExt> AsyncWrite for Async<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_write_impl(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(self.get_ref().flush())
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(self.get_ref().flush())
+    }
+}
+
+impl<T: AsFilelike + IoExt> AsyncWrite for &Async<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_write_impl(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(self.get_ref().flush())
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(self.get_ref().flush())
+    }
+}
+
+/// Test whether `handle` is ready for any of the operations in `events`,
+/// without blocking.
+fn is_ready<Filelike: AsFilelike>(handle: &Filelike, events: PollFlags) -> io::Result<bool> {
+    let mut fds = [PollFd::from_filelike(handle, events)];
+    loop {
+        match poll(&mut fds, Some(Duration::ZERO)) {
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+            otherwise => return otherwise.map(|n| n != 0),
+        }
+    }
+}
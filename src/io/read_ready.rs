@@ -2,7 +2,7 @@ use crate::io::IsReadWrite;
 use io_lifetimes::raw::{AsRawFilelike, FromRawFilelike};
 #[cfg(not(any(windows, target_os = "redox")))]
 use rustix::io::ioctl_fionread;
-use std::io::{self, Seek, SeekFrom, Stdin, StdinLock};
+use std::io::{self, Seek, Stdin, StdinLock};
 #[cfg(not(target_os = "redox"))]
 use std::net;
 use std::process::{ChildStderr, ChildStdout};
@@ -73,7 +73,7 @@ impl crate::io::ReadReady for std::fs::File {
                 // temporary `File` so that we can get a `&mut File` and call
                 // `seek` on it.
                 let mut tmp = unsafe { std::fs::File::from_raw_filelike(self.as_raw_filelike()) };
-                let current = tmp.seek(SeekFrom::Current(0));
+                let current = tmp.stream_position();
                 std::mem::forget(tmp);
                 return Ok(metadata.len() - current?);
             }
@@ -362,3 +362,80 @@ impl ReadReady for cap_std::fs_utf8::File {
         self.as_filelike_view::<std::fs::File>().num_ready_bytes()
     }
 }
+
+/// Implement `ReadReady` for `tokio::fs::File`.
+///
+/// This doesn't account for data which tokio has already read ahead into
+/// its own buffer, so it may under-report.
+#[cfg(feature = "tokio_impls")]
+impl ReadReady for tokio::fs::File {
+    #[inline]
+    fn num_ready_bytes(&self) -> io::Result<u64> {
+        use io_lifetimes::AsFilelike;
+        self.as_filelike_view::<std::fs::File>().num_ready_bytes()
+    }
+}
+
+/// Implement `ReadReady` for `tokio::net::TcpStream`.
+#[cfg(all(not(target_os = "redox"), feature = "tokio_impls"))]
+impl ReadReady for tokio::net::TcpStream {
+    #[inline]
+    fn num_ready_bytes(&self) -> io::Result<u64> {
+        use io_lifetimes::AsSocketlike;
+        self.as_socketlike_view::<net::TcpStream>()
+            .num_ready_bytes()
+    }
+}
+
+/// Implement `ReadReady` for `tokio::net::UnixStream`.
+#[cfg(all(unix, feature = "tokio_impls"))]
+impl ReadReady for tokio::net::UnixStream {
+    #[inline]
+    fn num_ready_bytes(&self) -> io::Result<u64> {
+        use io_lifetimes::AsSocketlike;
+        self.as_socketlike_view::<std::os::unix::net::UnixStream>()
+            .num_ready_bytes()
+    }
+}
+
+#[cfg(all(not(any(windows, target_os = "redox")), feature = "tokio_impls"))]
+impl ReadReady for tokio::net::unix::pipe::Receiver {
+    #[inline]
+    fn num_ready_bytes(&self) -> io::Result<u64> {
+        Ok(ioctl_fionread(self)?)
+    }
+}
+
+#[cfg(all(not(windows), feature = "tokio_impls"))]
+impl ReadReady for tokio::process::ChildStdout {
+    #[inline]
+    fn num_ready_bytes(&self) -> io::Result<u64> {
+        Ok(ioctl_fionread(self)?)
+    }
+}
+
+#[cfg(all(windows, feature = "tokio_impls"))]
+impl ReadReady for tokio::process::ChildStdout {
+    #[inline]
+    fn num_ready_bytes(&self) -> io::Result<u64> {
+        // Return the conservatively correct result.
+        Ok(0)
+    }
+}
+
+#[cfg(all(not(windows), feature = "tokio_impls"))]
+impl ReadReady for tokio::process::ChildStderr {
+    #[inline]
+    fn num_ready_bytes(&self) -> io::Result<u64> {
+        Ok(ioctl_fionread(self)?)
+    }
+}
+
+#[cfg(all(windows, feature = "tokio_impls"))]
+impl ReadReady for tokio::process::ChildStderr {
+    #[inline]
+    fn num_ready_bytes(&self) -> io::Result<u64> {
+        // Return the conservatively correct result.
+        Ok(0)
+    }
+}
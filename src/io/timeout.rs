@@ -0,0 +1,265 @@
+//! The `IoTimeoutExt` trait, and related utilities and impls.
+
+use crate::io::{poll, IoExt, PollFd, PollFlags};
+#[cfg(windows)]
+use io_lifetimes::AsSocketlike as AsPollable;
+#[cfg(not(windows))]
+use io_lifetimes::{AsFilelike, AsFilelike as AsPollable, AsSocketlike};
+use std::fmt;
+use std::io;
+use std::time::{Duration, Instant};
+
+/// The largest number of bytes to write after a handle reports that it's
+/// writable.
+///
+/// Writes of up to `PIPE_BUF` bytes to a writable pipe complete without
+/// blocking, so limit each write to that much. POSIX requires `PIPE_BUF` to
+/// be at least 512, and Linux uses 4096.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+pub(crate) const WRITE_CHUNK: usize = 4096;
+#[cfg(not(any(target_os = "android", target_os = "linux")))]
+pub(crate) const WRITE_CHUNK: usize = 512;
+
+/// The error payload for `io::ErrorKind::TimedOut` errors returned by
+/// [`IoTimeoutExt`] methods, recording how many bytes were transferred
+/// before the timeout expired.
+///
+/// It can be recovered from an `io::Error` with
+/// `err.get_ref().and_then(|e| e.downcast_ref::<TimedOut>())`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct TimedOut {
+    nbytes: usize,
+}
+
+impl TimedOut {
+    /// Return the number of bytes transferred before the timeout expired.
+    #[inline]
+    pub fn nbytes(&self) -> usize {
+        self.nbytes
+    }
+}
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "operation timed out after transferring {} bytes",
+            self.nbytes
+        )
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+/// Extension trait for I/O handles that supports reads and writes which give
+/// up after a timeout.
+///
+/// These work by waiting for the handle to become ready with [`poll`] before
+/// each read or write, so they don't depend on socket options or on the
+/// `NONBLOCK` flag, and they work on pipes, sockets, ttys, and character
+/// devices. On Windows, only sockets are supported.
+///
+/// On timeout, these fail with `io::ErrorKind::TimedOut`, with a
+/// [`TimedOut`] payload recording the number of bytes transferred.
+///
+/// Readiness is shared by all users of a handle, so if another thread or
+/// process reads from or writes to the same handle concurrently, these
+/// functions may still block.
+pub trait IoTimeoutExt: IoExt {
+    /// Like [`IoExt::read`], but fails if no data arrives within `timeout`.
+    fn read_with_timeout(&self, buf: &mut [u8], timeout: Duration) -> io::Result<usize>;
+
+    /// Like [`IoExt::read_exact`], but fails if `buf` isn't filled within
+    /// `timeout`.
+    fn read_exact_with_timeout(&self, buf: &mut [u8], timeout: Duration) -> io::Result<()>;
+
+    /// Like [`IoExt::write`], but fails if nothing can be written within
+    /// `timeout`.
+    ///
+    /// To avoid blocking, this may write fewer bytes than `write` would.
+    fn write_with_timeout(&self, buf: &[u8], timeout: Duration) -> io::Result<usize>;
+
+    /// Like [`IoExt::write_all`], but fails if `buf` isn't fully written
+    /// within `timeout`.
+    fn write_all_with_timeout(&self, buf: &[u8], timeout: Duration) -> io::Result<()>;
+}
+
+/// Implement `IoTimeoutExt` for any type which implements `AsRawFd`.
+#[cfg(not(windows))]
+impl<T: AsFilelike + AsSocketlike> IoTimeoutExt for T {
+    #[inline]
+    fn read_with_timeout(&self, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
+        read_until(self, buf, deadline(timeout))
+    }
+
+    #[inline]
+    fn read_exact_with_timeout(&self, buf: &mut [u8], timeout: Duration) -> io::Result<()> {
+        read_exact_until(self, buf, deadline(timeout))
+    }
+
+    #[inline]
+    fn write_with_timeout(&self, buf: &[u8], timeout: Duration) -> io::Result<usize> {
+        write_until(self, buf, deadline(timeout))
+    }
+
+    #[inline]
+    fn write_all_with_timeout(&self, buf: &[u8], timeout: Duration) -> io::Result<()> {
+        write_all_until(self, buf, deadline(timeout))
+    }
+}
+
+#[cfg(windows)]
+impl IoTimeoutExt for std::net::TcpStream {
+    #[inline]
+    fn read_with_timeout(&self, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
+        read_until(self, buf, deadline(timeout))
+    }
+
+    #[inline]
+    fn read_exact_with_timeout(&self, buf: &mut [u8], timeout: Duration) -> io::Result<()> {
+        read_exact_until(self, buf, deadline(timeout))
+    }
+
+    #[inline]
+    fn write_with_timeout(&self, buf: &[u8], timeout: Duration) -> io::Result<usize> {
+        write_until(self, buf, deadline(timeout))
+    }
+
+    #[inline]
+    fn write_all_with_timeout(&self, buf: &[u8], timeout: Duration) -> io::Result<()> {
+        write_all_until(self, buf, deadline(timeout))
+    }
+}
+
+#[cfg(windows)]
+#[cfg(feature = "cap_std_impls")]
+impl IoTimeoutExt for cap_std::net::TcpStream {
+    #[inline]
+    fn read_with_timeout(&self, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
+        read_until(self, buf, deadline(timeout))
+    }
+
+    #[inline]
+    fn read_exact_with_timeout(&self, buf: &mut [u8], timeout: Duration) -> io::Result<()> {
+        read_exact_until(self, buf, deadline(timeout))
+    }
+
+    #[inline]
+    fn write_with_timeout(&self, buf: &[u8], timeout: Duration) -> io::Result<usize> {
+        write_until(self, buf, deadline(timeout))
+    }
+
+    #[inline]
+    fn write_all_with_timeout(&self, buf: &[u8], timeout: Duration) -> io::Result<()> {
+        write_all_until(self, buf, deadline(timeout))
+    }
+}
+
+/// Compute the deadline for a timeout, or `None` if it's so far in the
+/// future that it can't be represented.
+fn deadline(timeout: Duration) -> Option<Instant> {
+    Instant::now().checked_add(timeout)
+}
+
+fn timed_out(nbytes: usize) -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, TimedOut { nbytes })
+}
+
+/// Wait until `handle` is ready for any of the operations in `events`, or
+/// until `deadline` passes.
+fn wait<Pollable: AsPollable>(
+    handle: &Pollable,
+    events: PollFlags,
+    deadline: Option<Instant>,
+) -> io::Result<()> {
+    loop {
+        let timeout = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        #[cfg(not(windows))]
+        let mut fds = [PollFd::from_filelike(handle, events)];
+        #[cfg(windows)]
+        let mut fds = [PollFd::from_socketlike(handle, events)];
+        match poll(&mut fds, timeout) {
+            Ok(0) => return Err(timed_out(0)),
+            Ok(_) => return Ok(()),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn read_until<Pollable: IoExt + AsPollable>(
+    handle: &Pollable,
+    buf: &mut [u8],
+    deadline: Option<Instant>,
+) -> io::Result<usize> {
+    loop {
+        wait(handle, PollFlags::IN, deadline)?;
+        match handle.read(buf) {
+            Err(err)
+                if err.kind() == io::ErrorKind::Interrupted
+                    || err.kind() == io::ErrorKind::WouldBlock => {}
+            otherwise => return otherwise,
+        }
+    }
+}
+
+fn read_exact_until<Pollable: IoExt + AsPollable>(
+    handle: &Pollable,
+    buf: &mut [u8],
+    deadline: Option<Instant>,
+) -> io::Result<()> {
+    let mut nread = 0;
+    while nread < buf.len() {
+        match read_until(handle, &mut buf[nread..], deadline) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "failed to fill whole buffer",
+                ))
+            }
+            Ok(n) => nread += n,
+            Err(err) if err.kind() == io::ErrorKind::TimedOut => return Err(timed_out(nread)),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+fn write_until<Pollable: IoExt + AsPollable>(
+    handle: &Pollable,
+    buf: &[u8],
+    deadline: Option<Instant>,
+) -> io::Result<usize> {
+    let buf = &buf[..buf.len().min(WRITE_CHUNK)];
+    loop {
+        wait(handle, PollFlags::OUT, deadline)?;
+        match handle.write(buf) {
+            Err(err)
+                if err.kind() == io::ErrorKind::Interrupted
+                    || err.kind() == io::ErrorKind::WouldBlock => {}
+            otherwise => return otherwise,
+        }
+    }
+}
+
+fn write_all_until<Pollable: IoExt + AsPollable>(
+    handle: &Pollable,
+    buf: &[u8],
+    deadline: Option<Instant>,
+) -> io::Result<()> {
+    let mut nwritten = 0;
+    while nwritten < buf.len() {
+        match write_until(handle, &buf[nwritten..], deadline) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "failed to write whole buffer",
+                ))
+            }
+            Ok(n) => nwritten += n,
+            Err(err) if err.kind() == io::ErrorKind::TimedOut => return Err(timed_out(nwritten)),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
@@ -46,3 +46,78 @@ fn allocate() {
     file.allocate(1024, 1024)
         .expect_err("allocate should fail on windows");
 }
+
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn supported(result: std::io::Result<()>) -> bool {
+    match result {
+        Ok(()) => true,
+        Err(err) if err.kind() == std::io::ErrorKind::Unsupported => false,
+        Err(err) => panic!("{}", err),
+    }
+}
+
+#[test]
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn allocate_keep_size_and_punch_hole() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = check!(OpenOptions::new()
+        .create_new(true)
+        .read(true)
+        .write(true)
+        .open(dir.path().join("file")));
+
+    if supported(file.allocate_keep_size(0, 8192)) {
+        assert_eq!(check!(file.metadata()).len(), 0);
+    }
+
+    check!(file.write_all_at(&[0xa5; 3 * 4096], 0));
+    if supported(file.punch_hole(4096, 4096)) {
+        assert_eq!(check!(file.metadata()).len(), 3 * 4096);
+        let mut buf = vec![0; 3 * 4096];
+        check!(file.read_exact_at(&mut buf, 0));
+        assert!(buf[..4096].iter().all(|b| *b == 0xa5));
+        assert!(buf[4096..2 * 4096].iter().all(|b| *b == 0));
+        assert!(buf[2 * 4096..].iter().all(|b| *b == 0xa5));
+    }
+
+    if supported(file.zero_range(2 * 4096, 2 * 4096)) {
+        assert_eq!(check!(file.metadata()).len(), 4 * 4096);
+        let mut buf = vec![0xff; 2 * 4096];
+        check!(file.read_exact_at(&mut buf, 2 * 4096));
+        assert!(buf.iter().all(|b| *b == 0));
+    }
+}
+
+#[test]
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn collapse_and_insert_range() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = check!(OpenOptions::new()
+        .create_new(true)
+        .read(true)
+        .write(true)
+        .open(dir.path().join("file")));
+
+    for (i, byte) in [b'a', b'b', b'c'].iter().enumerate() {
+        check!(file.write_all_at(&[*byte; 4096], i as u64 * 4096));
+    }
+
+    // Drop the first block.
+    if !supported(file.collapse_range(0, 4096)) {
+        return;
+    }
+    assert_eq!(check!(file.metadata()).len(), 2 * 4096);
+    let mut buf = [0; 1];
+    check!(file.read_exact_at(&mut buf, 0));
+    assert_eq!(buf, [b'b']);
+
+    // Put a hole back in front of it.
+    if !supported(file.insert_range(0, 4096)) {
+        return;
+    }
+    assert_eq!(check!(file.metadata()).len(), 3 * 4096);
+    check!(file.read_exact_at(&mut buf, 0));
+    assert_eq!(buf, [0]);
+    check!(file.read_exact_at(&mut buf, 4096));
+    assert_eq!(buf, [b'b']);
+}
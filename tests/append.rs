@@ -116,3 +116,27 @@ fn append() {
         &back
     );
 }
+
+/// `append_vectored` appends everything in one write or nothing.
+#[cfg(not(windows))]
+#[test]
+fn append_vectored_too_many_buffers() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = check!(OpenOptions::new()
+        .create_new(true)
+        .read(true)
+        .write(true)
+        .open(dir.path().join("file")));
+
+    let data = vec![b'x'; 3000];
+    let bufs: Vec<IoSlice> = data.chunks(1).map(IoSlice::new).collect();
+    assert_eq!(
+        file.append_vectored(&bufs).unwrap_err().kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+    assert_eq!(check!(file.metadata()).len(), 0);
+
+    // Up to the limit, it all goes in one write.
+    let nwritten = check!(file.append_vectored(&bufs[..16]));
+    assert_eq!(nwritten, 16);
+}
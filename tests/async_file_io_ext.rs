@@ -0,0 +1,139 @@
+#![cfg(feature = "cap_async_std_impls")]
+
+#[macro_use]
+mod sys_common;
+
+use async_std::fs::OpenOptions;
+use async_std::io::{ReadExt, SeekExt, WriteExt};
+use async_std::task::block_on;
+use std::io::{self, IoSliceMut, SeekFrom};
+use system_interface::fs::{Advice, AsyncFileIoExt};
+
+async fn open(dir: &tempfile::TempDir) -> async_std::fs::File {
+    check!(
+        OpenOptions::new()
+            .create_new(true)
+            .read(true)
+            .write(true)
+            .open(dir.path().join("file"))
+            .await
+    )
+}
+
+#[test]
+fn async_read_write_at() {
+    block_on(async {
+        let dir = tempfile::tempdir().unwrap();
+        let mut file = open(&dir).await;
+
+        // Writes buffered by the file are flushed first.
+        check!(file.write_all(b"abcdefghijklmnopqrstuvwxyz").await);
+
+        let mut buf = [0_u8; 4];
+        assert_eq!(check!(file.read_at(&mut buf, 4).await), 4);
+        assert_eq!(&buf, b"efgh");
+        check!(file.read_exact_at(&mut buf, 22).await);
+        assert_eq!(&buf, b"wxyz");
+        assert_eq!(
+            file.read_exact_at(&mut buf, 24).await.unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+
+        assert_eq!(check!(file.write_at(b"EF", 4).await), 2);
+        check!(file.write_all_at(b"GHIJ", 6).await);
+        assert_eq!(check!(file.seek(SeekFrom::Current(0)).await), 26);
+
+        let mut back = Vec::new();
+        assert_eq!(check!(file.read_to_end_at(&mut back, 2).await), 24);
+        assert_eq!(&back, b"cdEFGHIJklmnopqrstuvwxyz");
+    })
+}
+
+#[test]
+fn async_read_exact_vectored_at() {
+    block_on(async {
+        let dir = tempfile::tempdir().unwrap();
+        let mut file = open(&dir).await;
+        check!(file.write_all(b"abcdefghijklmnopqrstuvwxyz").await);
+
+        let mut buf0 = vec![0; 8];
+        let mut buf1 = vec![0; 8];
+        let mut bufs = vec![IoSliceMut::new(&mut buf0), IoSliceMut::new(&mut buf1)];
+        check!(file.read_exact_vectored_at(&mut bufs, 4).await);
+        assert_eq!(&buf0, b"efghijkl");
+        assert_eq!(&buf1, b"mnopqrst");
+
+        let mut bufs = vec![IoSliceMut::new(&mut buf0), IoSliceMut::new(&mut buf1)];
+        assert_eq!(
+            file.read_exact_vectored_at(&mut bufs, 12)
+                .await
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    })
+}
+
+#[test]
+fn async_append() {
+    block_on(async {
+        let dir = tempfile::tempdir().unwrap();
+        let mut file = open(&dir).await;
+        check!(file.write_all(b"abcdefghijklmnopqrstuvwxyz").await);
+        check!(file.seek(SeekFrom::Start(0)).await);
+
+        let nwritten0 = check!(file.append(b"EFGHIJKL").await);
+        let nwritten1 = check!(file.append(b"MNOPQRST").await);
+        assert_eq!(check!(file.seek(SeekFrom::Current(0)).await), 0);
+
+        let mut back = String::new();
+        check!(file.read_to_string(&mut back).await);
+        assert_eq!(
+            &"abcdefghijklmnopqrstuvwxyzEFGHIJKLMNOPQRST"[..26 + nwritten0 + nwritten1],
+            &back
+        );
+    })
+}
+
+#[test]
+#[cfg(not(target_os = "openbsd"))]
+fn async_allocate_and_advise() {
+    block_on(async {
+        let dir = tempfile::tempdir().unwrap();
+        let file = open(&dir).await;
+
+        check!(file.allocate(1024, 1024).await);
+        assert_eq!(check!(file.metadata().await).len(), 1024 + 1024);
+
+        check!(file.advise(0, 2048, Advice::Sequential).await);
+    })
+}
+
+#[test]
+fn cap_async_read_write_at() {
+    block_on(async {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = check!(
+            cap_async_std::fs::Dir::open_ambient_dir(
+                dir.path(),
+                cap_async_std::ambient_authority()
+            )
+            .await
+        );
+        let file = check!(
+            dir.open_with(
+                "file",
+                cap_async_std::fs::OpenOptions::new()
+                    .create_new(true)
+                    .read(true)
+                    .write(true)
+            )
+            .await
+        );
+
+        check!(file.write_all_at(b"abcdefgh", 0).await);
+        let mut buf = [0_u8; 4];
+        check!(file.read_exact_at(&mut buf, 2).await);
+        assert_eq!(&buf, b"cdef");
+    })
+}
@@ -0,0 +1,69 @@
+#![cfg(feature = "cap_async_std_impls")]
+
+#[macro_use]
+mod sys_common;
+
+use async_std::io::{ReadExt, WriteExt};
+use async_std::net::{TcpListener, TcpStream};
+use async_std::task::{block_on, sleep, spawn};
+use std::time::Duration;
+use system_interface::io::{AsyncPeek, AsyncReadReady};
+
+#[test]
+fn async_peek_tcp_stream() {
+    block_on(async {
+        let listener = check!(TcpListener::bind("127.0.0.1:0").await);
+        let mut client = check!(TcpStream::connect(check!(listener.local_addr())).await);
+        let (mut server, _) = check!(listener.accept().await);
+
+        let writer = spawn(async move {
+            sleep(Duration::from_millis(50)).await;
+            check!(client.write_all(b"hello").await);
+            client
+        });
+
+        assert_ne!(check!(server.wait_ready().await), 0);
+        let mut buf = [0_u8; 8];
+        let n = check!(AsyncPeek::peek(&mut server, &mut buf).await);
+        assert_eq!(&buf[..n], &b"hello"[..n]);
+
+        // Peeking doesn't consume the data.
+        let mut back = [0_u8; 5];
+        check!(server.read_exact(&mut back).await);
+        assert_eq!(&back, b"hello");
+
+        // Once the peer hangs up, the stream is ready, with nothing to read.
+        drop(writer.await);
+        assert_eq!(check!(server.wait_ready().await), 0);
+        assert_eq!(check!(AsyncPeek::peek(&mut server, &mut buf).await), 0);
+    })
+}
+
+#[cfg(unix)]
+#[test]
+fn async_peek_unix_stream() {
+    use async_std::os::unix::net::UnixStream;
+
+    block_on(async {
+        let (mut a, mut b) = check!(UnixStream::pair());
+
+        let writer = spawn(async move {
+            sleep(Duration::from_millis(50)).await;
+            check!(b.write_all(b"hello").await);
+            b
+        });
+
+        let mut buf = [0_u8; 8];
+        let n = check!(a.peek(&mut buf).await);
+        assert_ne!(n, 0);
+        assert_eq!(&buf[..n], &b"hello"[..n]);
+        assert_ne!(check!(a.wait_ready().await), 0);
+
+        let mut back = [0_u8; 5];
+        check!(a.read_exact(&mut back).await);
+        assert_eq!(&back, b"hello");
+
+        drop(writer.await);
+        assert_eq!(check!(a.peek(&mut buf).await), 0);
+    })
+}
@@ -0,0 +1,125 @@
+#![cfg(all(
+    any(target_os = "android", target_os = "linux"),
+    feature = "use_futures_io"
+))]
+
+#[macro_use]
+mod sys_common;
+
+use futures_lite::future::block_on;
+use futures_lite::{AsyncReadExt, AsyncWriteExt};
+use rustix::event::{eventfd, EventfdFlags};
+use std::os::unix::net::UnixStream;
+use std::thread::{sleep, spawn};
+use std::time::Duration;
+use system_interface::io::{Async, IoExt, ReadReady};
+
+#[test]
+fn async_read_and_peek() {
+    let (a, b) = check!(UnixStream::pair());
+    let mut a = check!(Async::new(a));
+
+    let writer = spawn(move || {
+        sleep(Duration::from_millis(50));
+        check!(b.write_all(b"hello"));
+        b
+    });
+
+    check!(block_on(a.readable()));
+    assert_eq!(check!(a.num_ready_bytes()), 5);
+
+    let mut buf = [0_u8; 2];
+    assert_eq!(check!(block_on(a.peek(&mut buf))), 2);
+    assert_eq!(&buf, b"he");
+
+    let mut buf = [0_u8; 5];
+    check!(block_on(a.read_exact(&mut buf)));
+    assert_eq!(&buf, b"hello");
+
+    // Closing the other end makes the handle readable, at the end of the
+    // stream.
+    drop(writer.join().unwrap());
+    let mut rest = Vec::new();
+    check!(block_on(a.read_to_end(&mut rest)));
+    assert!(rest.is_empty());
+}
+
+#[test]
+fn async_write() {
+    let (a, b) = check!(UnixStream::pair());
+    let a = check!(Async::new(a));
+
+    // Fill the socket buffer, so that the next write has to wait.
+    let mut total = 0;
+    loop {
+        match a.get_ref().try_write(&[0xa5; 4096]) {
+            Ok(n) => total += n,
+            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => break,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    let reader = spawn(move || {
+        sleep(Duration::from_millis(50));
+        let mut buf = vec![0_u8; total + 3];
+        check!(b.read_exact(&mut buf));
+        buf
+    });
+
+    check!(block_on((&a).write_all(b"end")));
+    let buf = reader.join().unwrap();
+    assert_eq!(&buf[total..], b"end");
+}
+
+#[test]
+fn async_eventfd() {
+    let efd = check!(eventfd(0, EventfdFlags::CLOEXEC));
+    let efd = check!(Async::new(std::fs::File::from(efd)));
+
+    let mut buf = [0_u8; 8];
+    assert_eq!(
+        efd.get_ref().try_read(&mut buf).unwrap_err().kind(),
+        std::io::ErrorKind::WouldBlock
+    );
+
+    let raw = check!(efd.get_ref().try_clone());
+    let writer = spawn(move || {
+        sleep(Duration::from_millis(50));
+        check!(raw.write_all(&3_u64.to_ne_bytes()));
+    });
+
+    check!(block_on((&efd).read_exact(&mut buf)));
+    assert_eq!(u64::from_ne_bytes(buf), 3);
+    writer.join().unwrap();
+}
+
+#[test]
+fn async_regular_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file");
+    let file = check!(std::fs::File::create(&path));
+    let mut file = check!(Async::new(file));
+    check!(block_on(file.write_all(b"data")));
+    check!(block_on(file.writable()));
+    let file = check!(file.into_inner());
+    drop(file);
+
+    let file = check!(Async::new(check!(std::fs::File::open(&path))));
+    let mut contents = String::new();
+    check!(block_on((&file).read_to_string(&mut contents)));
+    assert_eq!(contents, "data");
+}
+
+#[test]
+fn async_into_inner() {
+    let (a, b) = check!(UnixStream::pair());
+    let a = check!(Async::new(a));
+    let a = check!(a.into_inner());
+
+    // The handle can be registered again after being released.
+    let a = check!(Async::new(a));
+    check!(b.write_all(b"x"));
+    let mut buf = [0_u8; 1];
+    check!(block_on((&a).read_exact(&mut buf)));
+    assert_eq!(&buf, b"x");
+}
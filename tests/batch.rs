@@ -0,0 +1,179 @@
+#![cfg(all(
+    any(target_os = "android", target_os = "linux"),
+    feature = "use_io_uring"
+))]
+
+#[macro_use]
+mod sys_common;
+
+use std::io::IoSlice;
+use std::os::fd::AsRawFd;
+use system_interface::fs::{Batch, FileIoExt};
+
+fn batch_ops(io_uring: bool) {
+    let dir = tempfile::tempdir().unwrap();
+    let file = check!(std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(dir.path().join("file")));
+    let log = check!(std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(dir.path().join("log")));
+    check!(file.write_all_at(b"0123456789", 0));
+
+    // Use a small ring, so that the batch needs several rounds.
+    let mut batch = if io_uring {
+        check!(Batch::new(2))
+    } else {
+        Batch::without_io_uring()
+    };
+    let mut a = [0_u8; 4];
+    let mut b = [0_u8; 4];
+    let bufs = [IoSlice::new(b"ab"), IoSlice::new(b"cd")];
+    let ra = batch.read_at(&file, &mut a, 0);
+    let rb = batch.read_at(&file, &mut b, 8);
+    let w = batch.write_vectored_at(&file, &bufs, 20);
+    let ap = batch.append(&log, b"entry");
+    let al = batch.allocate(&log, 0, 4096);
+    assert_eq!(batch.len(), 5);
+
+    let results = check!(batch.submit());
+    assert!(batch.is_empty());
+    assert_eq!(results.len(), 5);
+    assert_eq!(*results[ra].as_ref().unwrap(), 4);
+    assert_eq!(*results[rb].as_ref().unwrap(), 2);
+    assert_eq!(*results[w].as_ref().unwrap(), 4);
+    assert_eq!(*results[ap].as_ref().unwrap(), 5);
+    assert_eq!(*results[al].as_ref().unwrap(), 0);
+    drop(batch);
+
+    assert_eq!(&a, b"0123");
+    assert_eq!(&b[..2], b"89");
+
+    let mut buf = [0_u8; 4];
+    check!(file.read_exact_at(&mut buf, 20));
+    assert_eq!(&buf, b"abcd");
+    assert_eq!(check!(log.metadata()).len(), 4096);
+    let mut buf = [0_u8; 5];
+    check!(log.read_exact_at(&mut buf, 0));
+    assert_eq!(&buf, b"entry");
+}
+
+#[test]
+fn batch_io_uring() {
+    batch_ops(true);
+}
+
+#[test]
+fn batch_without_io_uring() {
+    assert!(!Batch::without_io_uring().uses_io_uring());
+    batch_ops(false);
+}
+
+#[test]
+fn batch_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = check!(std::fs::File::create(dir.path().join("file")));
+
+    // The file is write-only, so reads fail individually.
+    let mut buf = [0_u8; 4];
+    let mut batch = check!(Batch::new(4));
+    let r = batch.read_at(&file, &mut buf, 0);
+    let w = batch.append(&file, b"ok");
+    let results = check!(batch.submit());
+    assert_eq!(
+        results[r].as_ref().unwrap_err().raw_os_error(),
+        Some(rustix::io::Errno::BADF.raw_os_error())
+    );
+    assert_eq!(*results[w].as_ref().unwrap(), 2);
+}
+
+/// If the ring breaks while an operation is in flight, the kernel may still
+/// write into its buffer, so `submit` must abort rather than return. The
+/// failure is injected in a child process, by replacing the ring's file
+/// descriptor while a read from an empty pipe is pending.
+#[test]
+fn batch_ring_failure_in_flight() {
+    use std::os::unix::process::ExitStatusExt;
+
+    if std::env::var_os("BATCH_RING_FAILURE_CHILD").is_some() {
+        ring_failure_child();
+        return;
+    }
+
+    let output = check!(std::process::Command::new(check!(std::env::current_exe()))
+        .args(["--exact", "batch_ring_failure_in_flight", "--nocapture"])
+        .env("BATCH_RING_FAILURE_CHILD", "1")
+        .output());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stdout.contains("io_uring is unavailable") {
+        return;
+    }
+    assert_eq!(output.status.signal(), Some(libc::SIGABRT), "{}", stderr);
+    assert!(stderr.contains("in flight"), "{}", stderr);
+}
+
+fn ring_failure_child() {
+    extern "C" fn interrupt(_: libc::c_int) {}
+
+    if !check!(Batch::new(2)).uses_io_uring() {
+        println!("io_uring is unavailable");
+        return;
+    }
+
+    // Install a handler without `SA_RESTART`, so that signals interrupt the
+    // wait for completions.
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = interrupt as *const () as libc::sighandler_t;
+        assert_eq!(
+            libc::sigaction(libc::SIGUSR1, &action, std::ptr::null_mut()),
+            0
+        );
+    }
+
+    let (reader, _writer) = check!(rustix::pipe::pipe());
+    let (tx, rx) = std::sync::mpsc::channel();
+    let _submitter = std::thread::spawn(move || {
+        let mut batch = check!(Batch::new(2));
+        let mut buf = [0_u8; 4];
+        batch.read_at(&reader, &mut buf, 0);
+        tx.send(unsafe { (libc::pthread_self(), libc::gettid()) })
+            .unwrap();
+        let _ = batch.submit();
+    });
+    let (thread, tid) = rx.recv().unwrap();
+
+    // Wait until the read is in flight, and the submitter is waiting for it.
+    let syscall = format!("/proc/self/task/{}/syscall", tid);
+    while !check!(std::fs::read_to_string(&syscall))
+        .starts_with(&format!("{} ", libc::SYS_io_uring_enter))
+    {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+
+    // Replace the ring with something that isn't one, and interrupt the
+    // wait so that `submit` tries to use it again.
+    let ring_fd = check!(std::fs::read_dir("/proc/self/fd"))
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let target = std::fs::read_link(entry.path()).ok()?;
+            (target.to_str() == Some("anon_inode:[io_uring]"))
+                .then(|| entry.file_name().to_str()?.parse::<libc::c_int>().ok())?
+        })
+        .next()
+        .unwrap();
+    let null = check!(std::fs::File::open("/dev/null"));
+    unsafe {
+        assert_ne!(libc::dup2(null.as_raw_fd(), ring_fd), -1);
+        assert_eq!(libc::pthread_kill(thread, libc::SIGUSR1), 0);
+    }
+    std::thread::sleep(std::time::Duration::from_secs(5));
+    panic!("submit didn't abort");
+}
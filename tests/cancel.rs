@@ -0,0 +1,76 @@
+#![cfg(not(windows))]
+
+#[macro_use]
+mod sys_common;
+
+use std::io::ErrorKind;
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+use system_interface::io::{CancelToken, IoCancelExt, IoExt};
+
+#[test]
+fn cancel_blocked_read() {
+    let (a, b) = check!(UnixStream::pair());
+    let token = check!(CancelToken::new());
+    assert!(!check!(token.is_cancelled()));
+
+    let canceller = token.canceller();
+    let thread = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        check!(canceller.cancel());
+    });
+
+    let mut buf = [0_u8; 8];
+    assert_eq!(
+        a.read_cancellable(&mut buf, &token).unwrap_err().kind(),
+        ErrorKind::Interrupted
+    );
+    thread.join().unwrap();
+    assert!(check!(token.is_cancelled()));
+
+    // The token stays cancelled until it's reset.
+    assert_eq!(
+        a.read_cancellable(&mut buf, &token).unwrap_err().kind(),
+        ErrorKind::Interrupted
+    );
+    check!(token.reset());
+    assert!(!check!(token.is_cancelled()));
+
+    // The handle is still usable.
+    check!(b.write_all(b"hello"));
+    assert_eq!(check!(a.read_cancellable(&mut buf, &token)), 5);
+    assert_eq!(&buf[..5], b"hello");
+    assert_eq!(check!(b.write_cancellable(b"!", &token)), 1);
+    assert_eq!(check!(a.read(&mut buf)), 1);
+}
+
+#[test]
+fn cancel_pipe_read() {
+    use std::process::{Command, Stdio};
+
+    let mut child = check!(Command::new("sleep")
+        .arg("10")
+        .stdout(Stdio::piped())
+        .spawn());
+    let stdout = child.stdout.take().unwrap();
+
+    let token = check!(CancelToken::new());
+    let canceller = token.canceller();
+    let thread = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        check!(canceller.cancel());
+    });
+
+    let mut buf = [0_u8; 8];
+    assert_eq!(
+        stdout
+            .read_cancellable(&mut buf, &token)
+            .unwrap_err()
+            .kind(),
+        ErrorKind::Interrupted
+    );
+    thread.join().unwrap();
+
+    check!(child.kill());
+    check!(child.wait());
+}
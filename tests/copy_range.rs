@@ -0,0 +1,98 @@
+#[macro_use]
+mod sys_common;
+
+use std::fs::OpenOptions;
+use std::io::SeekFrom;
+#[cfg(any(not(windows), feature = "cap_std_impls"))]
+use sys_common::io::tmpdir;
+use system_interface::fs::FileIoExt;
+
+fn open(path: &std::path::Path) -> std::fs::File {
+    check!(OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .read(true)
+        .write(true)
+        .open(path))
+}
+
+#[test]
+fn copy_range_to() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = open(&dir.path().join("src"));
+    let dst = open(&dir.path().join("dst"));
+    check!(src.write_all_at(b"abcdefghijklmnopqrstuvwxyz", 0));
+    check!(dst.write_all_at(b"0123456789", 0));
+    check!(FileIoExt::seek(&src, SeekFrom::Start(3)));
+
+    assert_eq!(check!(src.copy_range_to(&dst, 4, 2, 5)), 5);
+    let mut buf = String::new();
+    check!(dst.read_to_string_at(&mut buf, 0));
+    assert_eq!(buf, "01efghi789");
+
+    // The copy stops at the end of the source, and positions are unchanged.
+    assert_eq!(check!(src.copy_range_to(&dst, 20, 10, 100)), 6);
+    buf.clear();
+    check!(dst.read_to_string_at(&mut buf, 0));
+    assert_eq!(buf, "01efghi789uvwxyz");
+    assert_eq!(check!(src.stream_position()), 3);
+    assert_eq!(check!(dst.stream_position()), 0);
+
+    assert_eq!(check!(src.copy_range_to(&dst, 100, 0, 10)), 0);
+}
+
+#[test]
+fn copy_range_to_large() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = open(&dir.path().join("src"));
+    let dst = open(&dir.path().join("dst"));
+    let data = (0..300_000_u32).map(|i| i as u8).collect::<Vec<_>>();
+    check!(src.write_all_at(&data, 0));
+
+    assert_eq!(
+        check!(src.copy_range_to(&dst, 0, 0, data.len() as u64)),
+        data.len() as u64
+    );
+    let mut back = Vec::new();
+    check!(dst.read_to_end_at(&mut back, 0));
+    assert_eq!(back, data);
+}
+
+/// Copy between different filesystems, where Linux may refuse with `EXDEV`.
+#[test]
+#[cfg(target_os = "linux")]
+fn copy_range_to_other_filesystem() {
+    let shm = std::path::Path::new("/dev/shm");
+    if !shm.is_dir() {
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let other = tempfile::tempdir_in(shm).unwrap();
+    let src = open(&dir.path().join("src"));
+    let dst = open(&other.path().join("dst"));
+    check!(src.write_all_at(b"cross-device", 0));
+
+    assert_eq!(check!(src.copy_range_to(&dst, 0, 0, 12)), 12);
+    let mut buf = String::new();
+    check!(dst.read_to_string_at(&mut buf, 0));
+    assert_eq!(buf, "cross-device");
+}
+
+#[cfg(any(not(windows), feature = "cap_std_impls"))]
+#[test]
+fn cap_copy_range_to() {
+    let tmpdir = tmpdir();
+    let options = cap_std::fs::OpenOptions::new()
+        .create_new(true)
+        .read(true)
+        .write(true)
+        .clone();
+    let src = check!(tmpdir.open_with("src", &options));
+    let dst = check!(tmpdir.open_with("dst", &options));
+    check!(src.write_all_at(b"hello world", 0));
+
+    assert_eq!(check!(src.copy_range_to(&dst, 6, 0, 5)), 5);
+    let mut buf = String::new();
+    check!(dst.read_to_string_at(&mut buf, 0));
+    assert_eq!(buf, "world");
+}
@@ -0,0 +1,79 @@
+#[macro_use]
+mod sys_common;
+
+use std::io::ErrorKind;
+use system_interface::fs::{direct_io_alignment, AlignedBuf};
+
+#[test]
+fn aligned_buf() {
+    let buf = check!(AlignedBuf::new(8192, 4096));
+    assert_eq!(buf.len(), 8192);
+    assert_eq!(buf.align(), 4096);
+    assert_eq!(buf.as_ptr() as usize % 4096, 0);
+    assert!(buf.iter().all(|b| *b == 0));
+
+    let empty = check!(AlignedBuf::new(0, 512));
+    assert!(empty.is_empty());
+    assert_eq!(empty.as_ptr() as usize % 512, 0);
+
+    assert_eq!(
+        AlignedBuf::new(4096, 3000).unwrap_err().kind(),
+        ErrorKind::InvalidInput
+    );
+    assert_eq!(
+        AlignedBuf::new(100, 512).unwrap_err().kind(),
+        ErrorKind::InvalidInput
+    );
+}
+
+#[test]
+fn aligned_buf_for_direct_io() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = check!(std::fs::File::create(dir.path().join("file")));
+
+    let align = check!(direct_io_alignment(&file));
+    assert!(align.is_power_of_two());
+    let buf = check!(AlignedBuf::for_direct_io(&file, 1));
+    assert_eq!(buf.align(), align);
+    assert_eq!(buf.len(), align);
+    assert_eq!(buf.as_ptr() as usize % align, 0);
+}
+
+#[cfg(not(windows))]
+#[test]
+fn direct_io() {
+    use system_interface::fs::{FdFlags, FileIoExt, GetSetFdFlags};
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut file = check!(std::fs::OpenOptions::new()
+        .create_new(true)
+        .read(true)
+        .write(true)
+        .open(dir.path().join("file")));
+
+    match file.new_set_fd_flags(FdFlags::DIRECT) {
+        Ok(set_fd_flags) => match file.set_fd_flags(set_fd_flags) {
+            Ok(()) => (),
+            Err(err) if err.kind() == ErrorKind::Unsupported => return,
+            Err(err) => panic!("{}", err),
+        },
+        Err(err) if err.kind() == ErrorKind::Unsupported => return,
+        Err(err) => panic!("{}", err),
+    }
+    assert!(check!(file.get_fd_flags()).contains(FdFlags::DIRECT));
+
+    let mut buf = check!(AlignedBuf::for_direct_io(&file, 4096));
+    let len = buf.len();
+    buf.fill(0xa5);
+    assert_eq!(check!(file.write_at_aligned(&buf, len as u64)), len);
+
+    let mut back = check!(AlignedBuf::new(len, buf.align()));
+    assert_eq!(check!(file.read_at_aligned(&mut back, len as u64)), len);
+    assert_eq!(&back[..], &buf[..]);
+    assert_eq!(check!(file.read_at_aligned(&mut back, 0)), len);
+    assert!(back.iter().all(|b| *b == 0));
+
+    let err = file.write_at_aligned(&buf, 1).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    assert!(err.to_string().contains("alignment"), "{}", err);
+}
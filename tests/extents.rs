@@ -0,0 +1,82 @@
+#[macro_use]
+mod sys_common;
+
+use std::io::SeekFrom;
+use system_interface::fs::{ExtentKind, FileIoExt};
+
+const BLOCK: u64 = 64 * 1024;
+
+#[test]
+fn seek_data_and_hole() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = check!(std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(dir.path().join("sparse")));
+    check!(file.set_len(3 * BLOCK));
+    check!(file.write_all_at(&[0xa5; 4096], BLOCK));
+
+    // Data is found at or before the written region, and a hole at or after
+    // its end.
+    let data = check!(file.seek_data(0)).unwrap();
+    assert!(data <= BLOCK);
+    assert_eq!(check!(file.stream_position()), data);
+    let hole = check!(file.seek_hole(BLOCK)).unwrap();
+    assert!((BLOCK + 4096..=3 * BLOCK).contains(&hole));
+
+    // Past the end, there's nothing to find, and the position is unchanged.
+    check!(FileIoExt::seek(&file, SeekFrom::Start(7)));
+    assert_eq!(check!(file.seek_data(3 * BLOCK)), None);
+    assert_eq!(check!(file.seek_hole(3 * BLOCK)), None);
+    assert_eq!(check!(file.seek_data(u64::MAX)), None);
+    assert_eq!(check!(file.stream_position()), 7);
+}
+
+#[test]
+fn extents() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = check!(std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(dir.path().join("sparse")));
+    check!(file.set_len(3 * BLOCK));
+    check!(file.write_all_at(&[0xa5; 4096], BLOCK));
+
+    let extents = check!(file.extents())
+        .collect::<std::io::Result<Vec<_>>>()
+        .unwrap();
+
+    // The extents cover the whole file, in order, and alternate between data
+    // and holes.
+    let mut offset = 0;
+    for pair in extents.windows(2) {
+        assert_ne!(pair[0].kind(), pair[1].kind());
+    }
+    for extent in &extents {
+        assert_eq!(extent.offset(), offset);
+        assert!(!extent.is_empty());
+        offset += extent.len();
+    }
+    assert_eq!(offset, 3 * BLOCK);
+
+    // The written region is data.
+    let written = extents
+        .iter()
+        .find(|extent| extent.offset() <= BLOCK && extent.offset() + extent.len() > BLOCK)
+        .unwrap();
+    assert_eq!(written.kind(), ExtentKind::Data);
+    assert!(written.offset() + written.len() >= BLOCK + 4096);
+}
+
+#[test]
+fn extents_empty_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = check!(std::fs::File::create(dir.path().join("empty")));
+    assert_eq!(check!(file.extents()).count(), 0);
+    assert_eq!(check!(file.seek_data(0)), None);
+    assert_eq!(check!(file.seek_hole(0)), None);
+}
@@ -0,0 +1,127 @@
+#![cfg(target_os = "linux")]
+
+use std::io;
+use std::thread;
+use std::time::Duration;
+use sys_common::io::tmpdir;
+use system_interface::fs::{lease_break_signal, set_lease_break_signal, FileLeaseExt, LeaseKind};
+use system_interface::io::{poll, PollFd, PollFlags};
+#[macro_use]
+mod sys_common;
+
+/// Wait for the lease's break notification, failing after a generous timeout.
+fn wait_for_break<Lease: io_lifetimes::AsFilelike>(lease: &Lease) {
+    let mut fds = [PollFd::from_filelike(lease, PollFlags::IN)];
+    // The break signal itself may interrupt the `poll`.
+    loop {
+        match poll(&mut fds, Some(Duration::from_secs(10))) {
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            otherwise => break assert_eq!(check!(otherwise), 1),
+        }
+    }
+}
+
+/// Take a lease, or return from the test if the filesystem doesn't support
+/// leases.
+macro_rules! lease_or_skip {
+    ($file:expr, $kind:expr) => {
+        match $file.lease($kind) {
+            Err(err) if err.kind() == io::ErrorKind::Unsupported => return,
+            otherwise => check!(otherwise),
+        }
+    };
+}
+
+#[test]
+fn test_read_lease_break() {
+    let tmpdir = tmpdir();
+    check!(tmpdir.write("file", b"cached"));
+    let file = check!(tmpdir.open("file"));
+
+    let lease = lease_or_skip!(file, LeaseKind::Read);
+    assert_eq!(check!(file.get_lease()), Some(LeaseKind::Read));
+    assert!(!check!(lease.is_broken()));
+
+    // Readers don't break a read lease.
+    drop(check!(tmpdir.open("file")));
+    assert!(!check!(lease.is_broken()));
+
+    // A writer does, and its open waits until the lease is released.
+    let writer = thread::scope(|s| {
+        let writer =
+            s.spawn(|| tmpdir.open_with("file", cap_std::fs::OpenOptions::new().write(true)));
+        wait_for_break(&lease);
+        assert!(check!(lease.is_broken()));
+        assert_eq!(check!(file.get_lease()), None);
+        check!(lease.release());
+        writer.join().unwrap()
+    });
+    check!(writer);
+    assert_eq!(check!(file.get_lease()), None);
+}
+
+#[test]
+fn test_write_lease_downgrade() {
+    let tmpdir = tmpdir();
+    check!(tmpdir.write("file", b"cached"));
+    let file = check!(tmpdir.open("file"));
+
+    let mut lease = lease_or_skip!(file, LeaseKind::Write);
+    assert_eq!(check!(file.get_lease()), Some(LeaseKind::Write));
+
+    // A reader breaks a write lease, but downgrading satisfies it.
+    let reader = thread::scope(|s| {
+        let reader = s.spawn(|| tmpdir.open("file"));
+        wait_for_break(&lease);
+        assert_eq!(check!(file.get_lease()), Some(LeaseKind::Read));
+        check!(lease.downgrade());
+        reader.join().unwrap()
+    });
+    let _reader = check!(reader);
+    assert_eq!(lease.kind(), LeaseKind::Read);
+    assert_eq!(check!(file.get_lease()), Some(LeaseKind::Read));
+    assert!(!check!(lease.is_broken()));
+}
+
+#[test]
+fn test_lease_conflicts() {
+    let tmpdir = tmpdir();
+    check!(tmpdir.write("file", b"cached"));
+    let file = check!(tmpdir.open("file"));
+
+    // A write lease requires there to be no other open handles.
+    let other = check!(tmpdir.open("file"));
+    match file.lease(LeaseKind::Write) {
+        Err(err) if err.kind() == io::ErrorKind::Unsupported => return,
+        Err(err) => assert_eq!(err.kind(), io::ErrorKind::WouldBlock),
+        Ok(_) => panic!("write lease taken with another handle open"),
+    }
+    drop(other);
+
+    // A read lease requires there to be no writers.
+    let writer = check!(tmpdir.open_with("file", cap_std::fs::OpenOptions::new().write(true)));
+    assert_eq!(
+        file.lease(LeaseKind::Read).unwrap_err().kind(),
+        io::ErrorKind::WouldBlock
+    );
+    drop(writer);
+
+    drop(check!(file.lease(LeaseKind::Read)));
+    assert_eq!(check!(file.get_lease()), None);
+}
+
+#[test]
+fn test_lease_break_signal() {
+    assert_eq!(
+        set_lease_break_signal(0).unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+
+    // Choosing the signal already in use is fine, but once it's chosen, it
+    // can't be changed.
+    let signo = lease_break_signal();
+    check!(set_lease_break_signal(signo));
+    assert_eq!(lease_break_signal(), signo);
+    assert!(set_lease_break_signal(signo - 1).is_err());
+    assert_eq!(lease_break_signal(), signo);
+}
@@ -0,0 +1,171 @@
+use std::io;
+use sys_common::io::tmpdir;
+use system_interface::fs::{FileLockExt, LockKind};
+#[macro_use]
+mod sys_common;
+
+#[test]
+fn test_lock_conflicts() {
+    let tmpdir = tmpdir();
+    let a = check!(tmpdir.create("file"));
+    let b = check!(tmpdir.open("file"));
+
+    let guard = check!(a.lock_file(LockKind::Exclusive));
+    assert_eq!(guard.kind(), LockKind::Exclusive);
+    assert_eq!(
+        b.try_lock_file(LockKind::Shared).unwrap_err().kind(),
+        io::ErrorKind::WouldBlock
+    );
+    drop(guard);
+
+    let guard = check!(b.try_lock_file(LockKind::Shared));
+    let other = check!(a.try_lock_file(LockKind::Shared));
+    assert_eq!(
+        tmpdir
+            .open("file")
+            .unwrap()
+            .try_lock_file(LockKind::Exclusive)
+            .unwrap_err()
+            .kind(),
+        io::ErrorKind::WouldBlock
+    );
+    check!(guard.unlock());
+    check!(other.unlock());
+
+    drop(check!(b.try_lock_file(LockKind::Exclusive)));
+}
+
+#[test]
+fn test_lock_std_file() {
+    let tmp = check!(tempfile::NamedTempFile::new());
+    let a = tmp.as_file();
+    let b = check!(std::fs::File::open(tmp.path()));
+
+    let _guard = check!(a.try_lock_file(LockKind::Exclusive));
+    assert_eq!(
+        b.try_lock_file(LockKind::Exclusive).unwrap_err().kind(),
+        io::ErrorKind::WouldBlock
+    );
+}
+
+// Locks belong to the open file description, so they're shared with `dup`ed
+// handles, and closing one of those doesn't release them.
+#[cfg(not(windows))]
+#[test]
+fn test_lock_across_dup() {
+    let tmpdir = tmpdir();
+    let a = check!(tmpdir.create("file"));
+    let b = check!(tmpdir.open("file"));
+
+    let _guard = check!(a.lock_file(LockKind::Exclusive));
+    let dup = check!(a.try_clone());
+    drop(dup);
+
+    assert_eq!(
+        b.try_lock_file(LockKind::Shared).unwrap_err().kind(),
+        io::ErrorKind::WouldBlock
+    );
+}
+
+// Guards aren't counted, so upgrading through a second guard and then
+// dropping the first one unlocks the file.
+#[cfg(not(windows))]
+#[test]
+fn test_lock_upgrade_then_drop() {
+    let tmpdir = tmpdir();
+    let a = check!(tmpdir.create("file"));
+    let b = check!(tmpdir.open("file"));
+
+    let shared = check!(a.try_lock_file(LockKind::Shared));
+    let exclusive = check!(a.try_lock_file(LockKind::Exclusive));
+    assert_eq!(
+        b.try_lock_file(LockKind::Shared).unwrap_err().kind(),
+        io::ErrorKind::WouldBlock
+    );
+    drop(shared);
+    drop(check!(b.try_lock_file(LockKind::Exclusive)));
+    drop(exclusive);
+}
+
+#[cfg(any(windows, target_os = "android", target_os = "linux"))]
+#[test]
+fn test_lock_range() {
+    let tmpdir = tmpdir();
+    let (a, b) = open_read_write(&tmpdir);
+
+    let guard = check!(a.lock_range(0, 10, LockKind::Exclusive));
+    assert_eq!((guard.offset(), guard.len()), (0, 10));
+
+    // Adjacent ranges don't conflict.
+    drop(check!(b.try_lock_range(10, 10, LockKind::Exclusive)));
+    assert_eq!(
+        b.try_lock_range(5, 10, LockKind::Shared)
+            .unwrap_err()
+            .kind(),
+        io::ErrorKind::WouldBlock
+    );
+    drop(guard);
+    drop(check!(b.try_lock_range(5, 10, LockKind::Shared)));
+
+    // A length of zero extends through the end of the file.
+    let _guard = check!(a.try_lock_range(100, 0, LockKind::Exclusive));
+    assert_eq!(
+        b.try_lock_range(1 << 40, 1, LockKind::Shared)
+            .unwrap_err()
+            .kind(),
+        io::ErrorKind::WouldBlock
+    );
+    drop(check!(b.try_lock_range(50, 50, LockKind::Shared)));
+}
+
+#[cfg(any(target_os = "android", target_os = "linux"))]
+#[test]
+fn test_lock_range_across_dup() {
+    let tmpdir = tmpdir();
+    let (a, b) = open_read_write(&tmpdir);
+
+    let _guard = check!(a.lock_range(0, 10, LockKind::Shared));
+    let dup = check!(a.try_clone());
+    drop(dup);
+
+    assert_eq!(
+        b.try_lock_range(0, 1, LockKind::Exclusive)
+            .unwrap_err()
+            .kind(),
+        io::ErrorKind::WouldBlock
+    );
+}
+
+#[cfg(any(target_os = "android", target_os = "linux"))]
+#[test]
+fn test_lock_range_out_of_range() {
+    let tmpdir = tmpdir();
+    let (a, _b) = open_read_write(&tmpdir);
+
+    // Ranges which don't fit in a file offset are rejected, rather than
+    // being reported as unsupported.
+    for (offset, len) in [(u64::MAX, 1), (1, u64::MAX), (i64::MAX as u64, 2)] {
+        assert_eq!(
+            a.try_lock_range(offset, len, LockKind::Shared)
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+    drop(check!(a.try_lock_range(
+        i64::MAX as u64,
+        1,
+        LockKind::Shared
+    )));
+}
+
+/// Open two independent handles to the same file, both readable and
+/// writable, as range locks require.
+#[cfg(any(windows, target_os = "android", target_os = "linux"))]
+fn open_read_write(tmpdir: &cap_tempfile::TempDir) -> (cap_std::fs::File, cap_std::fs::File) {
+    let mut options = cap_std::fs::OpenOptions::new();
+    options.read(true).write(true).create(true);
+    let a = check!(tmpdir.open_with("file", &options));
+    let b = check!(tmpdir.open_with("file", &options));
+    (a, b)
+}
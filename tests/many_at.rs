@@ -0,0 +1,171 @@
+#[macro_use]
+mod sys_common;
+
+use std::fs::OpenOptions;
+use system_interface::fs::FileIoExt;
+
+fn open_file(dir: &tempfile::TempDir) -> std::fs::File {
+    check!(OpenOptions::new()
+        .create_new(true)
+        .read(true)
+        .write(true)
+        .open(dir.path().join("file")))
+}
+
+#[test]
+fn read_many_at() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = open_file(&dir);
+    check!(file.write_all_at(b"abcdefghijklmnopqrstuvwxyz", 0));
+
+    let mut a = [0_u8; 4];
+    let mut b = [0_u8; 2];
+    let mut c = [0_u8; 3];
+    let mut d = [0_u8; 5];
+    let mut e = [0_u8; 0];
+    let mut f = [0_u8; 4];
+    // Out of order, adjacent, overlapping, empty, and past EOF.
+    let mut reqs = [
+        (20, &mut a[..]),
+        (2, &mut b[..]),
+        (4, &mut c[..]),
+        (3, &mut d[..]),
+        (10, &mut e[..]),
+        (24, &mut f[..]),
+    ];
+    let results = file.read_many_at(&mut reqs);
+    let results: Vec<usize> = results.into_iter().map(|r| check!(r)).collect();
+    assert_eq!(results, [4, 2, 3, 5, 0, 2]);
+    assert_eq!(&a, b"uvwx");
+    assert_eq!(&b, b"cd");
+    assert_eq!(&c, b"efg");
+    assert_eq!(&d, b"defgh");
+    assert_eq!(&f[..2], b"yz");
+
+    assert!(file.read_many_at(&mut []).is_empty());
+}
+
+#[test]
+fn read_many_at_many() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = open_file(&dir);
+    let data: Vec<u8> = (0..5000_u32).map(|i| (i % 251) as u8).collect();
+    check!(file.write_all_at(&data, 0));
+
+    // Enough adjacent requests to need several vectored reads, and enough
+    // requests to need several rounds of a ring.
+    let mut back = vec![0_u8; 5000];
+    let mut reqs: Vec<(u64, &mut [u8])> = back
+        .chunks_mut(2)
+        .enumerate()
+        .map(|(i, buf)| (i as u64 * 2, buf))
+        .rev()
+        .collect();
+    for result in file.read_many_at(&mut reqs) {
+        assert_eq!(check!(result), 2);
+    }
+    assert_eq!(back, data);
+}
+
+#[test]
+fn write_many_at() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = open_file(&dir);
+    check!(file.write_all_at(b"abcdefghijklmnopqrstuvwxyz", 0));
+
+    let reqs = [
+        (20, &b"UVW"[..]),
+        (2, &b"CD"[..]),
+        (4, &b"EF"[..]),
+        (10, &b""[..]),
+        (28, &b"!"[..]),
+    ];
+    let results = file.write_many_at(&reqs);
+    let results: Vec<usize> = results.into_iter().map(|r| check!(r)).collect();
+    assert_eq!(results, [3, 2, 2, 0, 1]);
+
+    let mut back = Vec::new();
+    check!(file.read_to_end_at(&mut back, 0));
+    assert_eq!(&back, b"abCDEFghijklmnopqrstUVWxyz\0\0!");
+}
+
+#[test]
+fn many_at_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    check!(std::fs::write(dir.path().join("file"), b"abcdef"));
+    let file = check!(std::fs::File::open(dir.path().join("file")));
+
+    // Each request gets its own error.
+    let results = file.write_many_at(&[(0, &b"ab"[..]), (2, &b"cd"[..])]);
+    assert_eq!(results.len(), 2);
+    for result in results {
+        assert!(result.is_err());
+    }
+}
+
+/// Requests in a run of adjacent ranges which can't be read together are
+/// redone one at a time, so that each gets its own result.
+#[test]
+fn read_many_at_split_failures() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = open_file(&dir);
+    check!(file.write_all_at(b"abcdefghijklmnopqrstuvwxyz", 0));
+
+    // A read which ends past `i64::MAX` fails, so the run at the end fails
+    // as a whole, though its first request succeeds on its own.
+    let end = i64::MAX as u64;
+    let mut a = [0_u8; 2];
+    let mut b = [0_u8; 2];
+    let mut c = [0_u8; 1];
+    let mut d = [0_u8; 1];
+    let mut reqs = [
+        (end, &mut d[..]),
+        (2, &mut b[..]),
+        (end - 1, &mut c[..]),
+        (0, &mut a[..]),
+    ];
+    let results = file.read_many_at(&mut reqs);
+    assert_eq!(
+        results[0].as_ref().unwrap_err().kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+    assert_eq!(*results[1].as_ref().unwrap(), 2);
+    assert_eq!(*results[2].as_ref().unwrap(), 0);
+    assert_eq!(*results[3].as_ref().unwrap(), 2);
+    assert_eq!(&a, b"ab");
+    assert_eq!(&b, b"cd");
+}
+
+/// As with reads, requests in a run of adjacent ranges which can't be written
+/// together are redone one at a time.
+#[test]
+fn write_many_at_split_failures() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = open_file(&dir);
+    check!(file.write_all_at(b"abcdefghijklmnopqrstuvwxyz", 0));
+
+    let end = i64::MAX as u64;
+    let reqs = [
+        (end, &b"!"[..]),
+        (2, &b"CD"[..]),
+        (end - 1, &b"!"[..]),
+        (0, &b"AB"[..]),
+    ];
+    let results = file.write_many_at(&reqs);
+    assert_eq!(
+        results[0].as_ref().unwrap_err().kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+    assert_eq!(*results[1].as_ref().unwrap(), 2);
+    // Depending on the filesystem's maximum file size, the write just before
+    // `i64::MAX` may succeed or fail, but it's performed on its own, so it
+    // isn't rejected for ending past `i64::MAX`.
+    if let Err(err) = &results[2] {
+        assert_ne!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+    assert_eq!(*results[3].as_ref().unwrap(), 2);
+
+    let mut back = [0_u8; 6];
+    check!(file.read_exact_at(&mut back, 0));
+    assert_eq!(&back, b"ABCDef");
+}
@@ -0,0 +1,109 @@
+use std::fs::{self, OpenOptions};
+use std::io;
+use system_interface::fs::{Advice, FileIoExt, MapMode, MappedRegion};
+#[macro_use]
+mod sys_common;
+
+/// Contents long enough to span several pages.
+fn contents() -> Vec<u8> {
+    (0..100_000_u32).map(|i| (i % 251) as u8).collect()
+}
+
+#[test]
+fn test_map_read_only() {
+    let dir = check!(tempfile::tempdir());
+    let path = dir.path().join("file");
+    let contents = contents();
+    check!(fs::write(&path, &contents));
+    let file = check!(fs::File::open(&path));
+
+    // Offsets needn't be aligned.
+    let mut region = check!(unsafe { MappedRegion::new(&file, 12345, 50_000, MapMode::ReadOnly) });
+    assert_eq!(region.mode(), MapMode::ReadOnly);
+    assert_eq!(region.len(), 50_000);
+    assert_eq!(&region[..], &contents[12345..62345]);
+    assert!(region.as_mut_slice().is_none());
+
+    // The mapping outlives the handle.
+    drop(file);
+    assert_eq!(region[0], contents[12345]);
+
+    for advice in [
+        Advice::Normal,
+        Advice::Sequential,
+        Advice::Random,
+        Advice::WillNeed,
+        Advice::DontNeed,
+        Advice::NoReuse,
+    ] {
+        check!(region.advise(100, 20_000, advice));
+    }
+    assert_eq!(&region[..], &contents[12345..62345]);
+}
+
+#[test]
+fn test_map_shared() {
+    let dir = check!(tempfile::tempdir());
+    let path = dir.path().join("file");
+    let contents = contents();
+    check!(fs::write(&path, &contents));
+    let file = check!(OpenOptions::new().read(true).write(true).open(&path));
+
+    let mut region = check!(unsafe { MappedRegion::new(&file, 5000, 10_000, MapMode::Shared) });
+    region.as_mut_slice().unwrap()[..5].copy_from_slice(b"hello");
+    check!(region.flush_range(0, 5));
+    check!(region.flush());
+
+    let mut buf = [0; 5];
+    check!(file.read_exact_at(&mut buf, 5000));
+    assert_eq!(&buf, b"hello");
+
+    // Writes through the file are visible through a shared mapping.
+    check!(file.write_all_at(b"world", 6000));
+    assert_eq!(&region[1000..1005], b"world");
+}
+
+#[test]
+fn test_map_private() {
+    let dir = check!(tempfile::tempdir());
+    let path = dir.path().join("file");
+    let contents = contents();
+    check!(fs::write(&path, &contents));
+    let file = check!(fs::File::open(&path));
+
+    let mut region = check!(unsafe { MappedRegion::new(&file, 0, 4, MapMode::Private) });
+    region.as_mut_slice().unwrap().copy_from_slice(b"abcd");
+    assert_eq!(&region[..], b"abcd");
+    check!(region.flush());
+
+    let mut buf = [0; 4];
+    check!(file.read_exact_at(&mut buf, 0));
+    assert_eq!(&buf[..], &contents[..4]);
+}
+
+#[test]
+fn test_map_ranges() {
+    let dir = check!(tempfile::tempdir());
+    let path = dir.path().join("file");
+    check!(fs::write(&path, contents()));
+    let file = check!(fs::File::open(&path));
+
+    let region = check!(unsafe { MappedRegion::new(&file, 0, 0, MapMode::ReadOnly) });
+    assert!(region.is_empty());
+    assert_eq!(&region[..], b"");
+    check!(region.flush());
+
+    let region = check!(unsafe { MappedRegion::new(&file, 100, 1000, MapMode::ReadOnly) });
+    assert_eq!(
+        region.flush_range(500, 501).unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+    assert_eq!(
+        region
+            .advise(usize::MAX, 2, Advice::WillNeed)
+            .unwrap_err()
+            .kind(),
+        io::ErrorKind::InvalidInput
+    );
+    check!(region.advise(1000, 0, Advice::WillNeed));
+}
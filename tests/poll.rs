@@ -0,0 +1,51 @@
+#[macro_use]
+mod sys_common;
+
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+use system_interface::io::{poll, PollFd, PollFlags};
+
+#[test]
+fn poll_tcp_stream() {
+    let listener = check!(TcpListener::bind("127.0.0.1:0"));
+    let mut client = check!(TcpStream::connect(check!(listener.local_addr())));
+    let (server, _) = check!(listener.accept());
+
+    // Nothing has been written yet, so the server isn't readable.
+    let mut fds = [PollFd::from_socketlike(&server, PollFlags::IN)];
+    assert_eq!(check!(poll(&mut fds, Some(Duration::ZERO))), 0);
+    assert!(fds[0].revents().is_empty());
+
+    // The client is writable.
+    let mut fds = [PollFd::from_socketlike(&client, PollFlags::OUT)];
+    assert_eq!(check!(poll(&mut fds, Some(Duration::ZERO))), 1);
+    assert!(fds[0].revents().contains(PollFlags::OUT));
+
+    check!(client.write_all(b"hello"));
+
+    // Now the server is readable.
+    let mut fds = [
+        PollFd::from_socketlike(&client, PollFlags::IN),
+        PollFd::from_socketlike(&server, PollFlags::IN),
+    ];
+    assert_eq!(check!(poll(&mut fds, None)), 1);
+    assert!(fds[0].revents().is_empty());
+    assert!(fds[1].revents().contains(PollFlags::IN));
+
+    fds[1].clear_revents();
+    assert!(fds[1].revents().is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn poll_unix_stream_hangup() {
+    use std::os::unix::net::UnixStream;
+
+    let (a, b) = check!(UnixStream::pair());
+    drop(b);
+
+    let mut fds = [PollFd::from_filelike(&a, PollFlags::IN)];
+    assert_eq!(check!(poll(&mut fds, Some(Duration::from_secs(1)))), 1);
+    assert!(fds[0].revents().contains(PollFlags::HUP));
+}
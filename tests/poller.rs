@@ -0,0 +1,92 @@
+#![cfg(any(target_os = "android", target_os = "linux"))]
+
+#[macro_use]
+mod sys_common;
+
+use std::io::Write;
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+use system_interface::io::{Events, PollFlags, Poller, Trigger};
+
+#[test]
+fn poller_level_and_edge() {
+    let poller = check!(Poller::new());
+    let mut events = Events::with_capacity(8);
+
+    let (a, mut b) = check!(UnixStream::pair());
+    let (c, mut d) = check!(UnixStream::pair());
+    check!(poller.add(&a, 1, PollFlags::IN, Trigger::Level));
+    check!(poller.add(&c, 2, PollFlags::IN, Trigger::Edge));
+
+    assert_eq!(check!(poller.wait(&mut events, Some(Duration::ZERO))), 0);
+
+    check!(b.write_all(b"a"));
+    check!(d.write_all(b"c"));
+
+    let mut tokens = Vec::new();
+    check!(poller.wait(&mut events, Some(Duration::from_secs(1))));
+    for event in events.iter() {
+        assert!(event.readiness().contains(PollFlags::IN));
+        tokens.push(event.token());
+    }
+    tokens.sort();
+    assert_eq!(tokens, [1, 2]);
+
+    // Nothing has been read, so only the level-triggered handle is reported
+    // again.
+    assert_eq!(check!(poller.wait(&mut events, Some(Duration::ZERO))), 1);
+    assert_eq!(events.iter().next().unwrap().token(), 1);
+
+    check!(poller.delete(&a));
+    assert_eq!(check!(poller.wait(&mut events, Some(Duration::ZERO))), 0);
+
+    // Modifying the registration re-arms it.
+    check!(poller.modify(&c, 3, PollFlags::IN, Trigger::Level));
+    assert_eq!(check!(poller.wait(&mut events, Some(Duration::ZERO))), 1);
+    assert_eq!(events.iter().next().unwrap().token(), 3);
+}
+
+#[test]
+fn poller_regular_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = check!(std::fs::File::create(dir.path().join("file")));
+
+    let poller = check!(Poller::new());
+    let mut events = Events::with_capacity(8);
+
+    check!(poller.add(&file, 7, PollFlags::OUT, Trigger::Edge));
+    assert_eq!(check!(poller.wait(&mut events, None)), 1);
+    let event = events.iter().next().unwrap();
+    assert_eq!(event.token(), 7);
+    assert_eq!(event.readiness(), PollFlags::OUT);
+    assert_eq!(check!(poller.wait(&mut events, Some(Duration::ZERO))), 0);
+
+    check!(poller.delete(&file));
+}
+
+#[test]
+fn poller_waker() {
+    let poller = check!(Poller::new());
+    let mut events = Events::with_capacity(8);
+
+    let waker = poller.waker();
+    let thread = std::thread::spawn(move || check!(waker.wake()));
+    assert_eq!(check!(poller.wait(&mut events, None)), 0);
+    thread.join().unwrap();
+
+    // The wake-up was consumed.
+    assert_eq!(check!(poller.wait(&mut events, Some(Duration::ZERO))), 0);
+}
+
+#[test]
+fn poller_reserved_token() {
+    let poller = check!(Poller::new());
+    let (a, _b) = check!(UnixStream::pair());
+    assert_eq!(
+        poller
+            .add(&a, u64::MAX, PollFlags::IN, Trigger::Level)
+            .unwrap_err()
+            .kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+}
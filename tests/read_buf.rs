@@ -0,0 +1,90 @@
+#[macro_use]
+mod sys_common;
+
+use std::fs::OpenOptions;
+use std::mem::MaybeUninit;
+use system_interface::fs::FileIoExt;
+use system_interface::io::{BorrowedBuf, IoExt};
+
+fn file_with(contents: &[u8]) -> (tempfile::TempDir, std::fs::File) {
+    let dir = tempfile::tempdir().unwrap();
+    let file = check!(OpenOptions::new()
+        .create_new(true)
+        .read(true)
+        .write(true)
+        .open(dir.path().join("file")));
+    check!(file.write_all_at(contents, 0));
+    (dir, file)
+}
+
+#[test]
+fn read_buf_at() {
+    let (_dir, file) = file_with(b"abcdefghijklmnopqrstuvwxyz");
+
+    let mut storage = [MaybeUninit::<u8>::uninit(); 10];
+    let mut buf = BorrowedBuf::from(&mut storage[..]);
+    assert_eq!(buf.capacity(), 10);
+    assert!(buf.is_empty());
+
+    check!(file.read_buf_at(buf.unfilled(), 4));
+    assert_eq!(buf.filled(), b"efghijklmn");
+    assert!(buf.init_len() >= buf.len());
+
+    // A full cursor reads nothing.
+    check!(file.read_buf_at(buf.unfilled(), 0));
+    assert_eq!(buf.filled(), b"efghijklmn");
+
+    // Reads append after what's already filled, and stop at EOF.
+    buf.clear();
+    buf.unfilled().append(b"xy");
+    let mut cursor = buf.unfilled();
+    check!(file.read_buf_at(cursor.reborrow(), 22));
+    assert_eq!(cursor.written(), 4);
+    check!(file.read_buf_at(cursor.reborrow(), 26));
+    assert_eq!(cursor.written(), 4);
+    assert_eq!(buf.filled(), b"xywxyz");
+}
+
+#[test]
+fn read_buf() {
+    let (_dir, file) = file_with(b"abcdefghijklmnopqrstuvwxyz");
+
+    let mut storage = [0_u8; 8];
+    let mut buf = BorrowedBuf::from(&mut storage[..]);
+    assert_eq!(buf.init_len(), 8);
+
+    let mut cursor = buf.unfilled();
+    while cursor.capacity() > 0 {
+        check!(file.read_buf(cursor.reborrow()));
+    }
+    assert_eq!(buf.filled(), b"abcdefgh");
+    assert_eq!(check!(file.stream_position()), 8);
+}
+
+#[test]
+fn borrowed_buf_init_tracking() {
+    let mut storage = [MaybeUninit::<u8>::uninit(); 16];
+    let mut buf = BorrowedBuf::from(&mut storage[..]);
+
+    let mut cursor = buf.unfilled();
+    assert!(cursor.init_mut().is_empty());
+    cursor.ensure_init();
+    assert_eq!(cursor.init_mut(), &[0; 16]);
+    cursor.init_mut()[..3].copy_from_slice(b"abc");
+    cursor.advance(3);
+    assert_eq!(buf.filled(), b"abc");
+    assert_eq!(buf.init_len(), 16);
+
+    // Clearing keeps the initialized bytes.
+    buf.clear();
+    assert_eq!(buf.init_len(), 16);
+    assert_eq!(buf.unfilled().init_mut().len(), 16);
+}
+
+#[test]
+#[should_panic]
+fn borrowed_cursor_advance_past_init() {
+    let mut storage = [MaybeUninit::<u8>::uninit(); 4];
+    let mut buf = BorrowedBuf::from(&mut storage[..]);
+    buf.unfilled().advance(1);
+}
@@ -48,3 +48,201 @@ fn read_to_string_at_error() {
     assert!(file.read_to_string_at(&mut buf, 4).is_err());
     assert!(buf.is_empty());
 }
+
+#[test]
+fn read_to_end_at_appends() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = check!(OpenOptions::new()
+        .create_new(true)
+        .read(true)
+        .write(true)
+        .open(dir.path().join("file")));
+    check!(write!(&file, "abcdefghijklmnopqrstuvwxyz"));
+    let mut buf = b"xy".to_vec();
+    assert_eq!(check!(file.read_to_end_at(&mut buf, 20)), 6);
+    assert_eq!(&buf, b"xyuvwxyz");
+    assert_eq!(check!(file.read_to_end_at(&mut buf, 26)), 0);
+    assert_eq!(check!(file.read_to_end_at(&mut buf, 100)), 0);
+    assert_eq!(&buf, b"xyuvwxyz");
+}
+
+#[test]
+fn read_to_end_at_grown() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = check!(OpenOptions::new()
+        .create_new(true)
+        .read(true)
+        .write(true)
+        .open(dir.path().join("file")));
+    check!(write!(&file, "abcdefghij"));
+    let mut buf = Vec::new();
+    assert_eq!(check!(file.read_to_end_at(&mut buf, 4)), 6);
+
+    // Data appended since the last read is picked up, even though `buf`
+    // already has the capacity the old size suggested.
+    check!(file.write_all_at(b"klmnopqrstuvwxyz", 10));
+    assert_eq!(check!(file.read_to_end_at(&mut buf, 10)), 16);
+    assert_eq!(&buf, b"efghijklmnopqrstuvwxyz");
+
+    let mut buf = String::new();
+    assert_eq!(check!(file.read_to_string_at(&mut buf, 20)), 6);
+    check!(file.write_all_at(b"0123456789", 26));
+    assert_eq!(check!(file.read_to_string_at(&mut buf, 26)), 10);
+    assert_eq!(buf, "uvwxyz0123456789");
+}
+
+#[test]
+fn read_to_end_at_growing() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = check!(OpenOptions::new()
+        .create_new(true)
+        .read(true)
+        .write(true)
+        .open(dir.path().join("file")));
+    check!(file.write_all_at(&[0; 4096], 0));
+
+    // Append to the file while it's being read. Whatever the read sees must
+    // be at least what was there when it started, and a prefix of what's
+    // there when it's done.
+    let done = AtomicBool::new(false);
+    let buf = std::thread::scope(|scope| {
+        scope.spawn(|| {
+            let mut len = 4096;
+            while !done.load(Ordering::Relaxed) {
+                check!(file.write_all_at(&[len as u8; 512], len));
+                len += 512;
+            }
+        });
+        let mut buf = Vec::new();
+        for _ in 0..16 {
+            buf.clear();
+            check!(file.read_to_end_at(&mut buf, 0));
+        }
+        done.store(true, Ordering::Relaxed);
+        buf
+    });
+
+    let mut all = Vec::new();
+    check!(file.read_to_end_at(&mut all, 0));
+    assert!(buf.len() >= 4096);
+    assert!(all.starts_with(&buf));
+}
+
+// Files in procfs report a size of zero, and can be larger than any initial
+// buffer.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+#[test]
+fn read_to_end_at_procfs() {
+    let file = check!(std::fs::File::open("/proc/self/status"));
+    assert_eq!(check!(file.metadata()).len(), 0);
+    let mut buf = String::new();
+    let n = check!(file.read_to_string_at(&mut buf, 0));
+    assert_eq!(n, buf.len());
+    assert!(buf.starts_with("Name:"), "{:?}", buf);
+    assert!(buf.ends_with('\n'));
+
+    let file = check!(std::fs::File::open("/proc/self/smaps"));
+    let mut buf = Vec::new();
+    let n = check!(file.read_to_end_at(&mut buf, 0));
+    assert_eq!(n, buf.len());
+    assert!(n > 0);
+    assert!(buf.ends_with(b"\n"));
+}
+
+#[test]
+fn read_to_end_at_limited() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = check!(OpenOptions::new()
+        .create_new(true)
+        .read(true)
+        .write(true)
+        .open(dir.path().join("file")));
+    check!(write!(&file, "abcdefghijklmnopqrstuvwxyz"));
+
+    let mut buf = b"0".to_vec();
+    assert_eq!(check!(file.read_to_end_at_limited(&mut buf, 4, 100)), 22);
+    assert_eq!(&buf, b"0efghijklmnopqrstuvwxyz");
+
+    // Hitting the limit exactly isn't an error.
+    buf.clear();
+    assert_eq!(check!(file.read_to_end_at_limited(&mut buf, 16, 10)), 10);
+    assert_eq!(&buf, b"qrstuvwxyz");
+
+    // Exceeding it is, and leaves the bytes up to the limit in `buf`.
+    buf.clear();
+    let err = file.read_to_end_at_limited(&mut buf, 16, 9).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::FileTooLarge);
+    assert_eq!(&buf, b"qrstuvwxy");
+
+    buf.clear();
+    let err = file.read_to_end_at_limited(&mut buf, 0, 0).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::FileTooLarge);
+    assert!(buf.is_empty());
+    assert_eq!(check!(file.read_to_end_at_limited(&mut buf, 26, 0)), 0);
+}
+
+#[test]
+fn read_to_end_limited() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = check!(OpenOptions::new()
+        .create_new(true)
+        .read(true)
+        .write(true)
+        .open(dir.path().join("file")));
+    check!(write!(&file, "abcdefghijklmnopqrstuvwxyz"));
+
+    check!(file.seek(std::io::SeekFrom::Start(0)));
+    let mut buf = Vec::new();
+    let err = file.read_to_end_limited(&mut buf, 20).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::FileTooLarge);
+    // The byte read past the limit is kept, since it's been consumed.
+    assert_eq!(&buf, b"abcdefghijklmnopqrstu");
+    assert_eq!(check!(file.stream_position()), 21);
+
+    check!(file.seek(std::io::SeekFrom::Start(0)));
+    let mut buf = Vec::new();
+    assert_eq!(check!(file.read_to_end_limited(&mut buf, 26)), 26);
+    assert_eq!(&buf, b"abcdefghijklmnopqrstuvwxyz");
+}
+
+#[test]
+fn read_to_string_limited() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = check!(OpenOptions::new()
+        .create_new(true)
+        .read(true)
+        .write(true)
+        .open(dir.path().join("file")));
+    check!(write!(&file, "añb"));
+
+    let mut buf = String::new();
+    assert_eq!(check!(file.read_to_string_at_limited(&mut buf, 0, 4)), 4);
+    assert_eq!(buf, "añb");
+
+    // A limit which splits a character keeps the bytes before it.
+    buf.clear();
+    let err = file.read_to_string_at_limited(&mut buf, 0, 2).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::FileTooLarge);
+    assert_eq!(buf, "a");
+
+    check!(file.seek(std::io::SeekFrom::Start(0)));
+    buf.clear();
+    let err = file.read_to_string_limited(&mut buf, 3).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::FileTooLarge);
+    assert_eq!(buf, "añb");
+
+    check!(file.seek(std::io::SeekFrom::Start(0)));
+    buf.clear();
+    let err = file.read_to_string_limited(&mut buf, 1).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::FileTooLarge);
+    assert_eq!(buf, "a");
+
+    // Invalid UTF-8 is still an error.
+    check!(file.write_all_at(b"\xff", 1));
+    buf.clear();
+    let err = file.read_to_string_at_limited(&mut buf, 0, 4).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(buf.is_empty());
+}
@@ -0,0 +1,68 @@
+#[macro_use]
+mod sys_common;
+
+use std::fs::OpenOptions;
+use std::io::{IoSlice, IoSliceMut};
+use system_interface::fs::{FileIoExt, RwFlags};
+
+#[test]
+fn vectored_at_with_flags() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = check!(OpenOptions::new()
+        .create_new(true)
+        .read(true)
+        .write(true)
+        .open(dir.path().join("file")));
+
+    let bufs = [IoSlice::new(b"abc"), IoSlice::new(b"def")];
+    assert_eq!(
+        check!(file.write_vectored_at_with_flags(&bufs, 0, RwFlags::DSYNC)),
+        6
+    );
+    let bufs = [IoSlice::new(b"ghi")];
+    assert_eq!(
+        check!(file.write_vectored_at_with_flags(&bufs, 6, RwFlags::SYNC | RwFlags::HIPRI)),
+        3
+    );
+    assert_eq!(
+        check!(file.write_vectored_at_with_flags(&bufs, 9, RwFlags::empty())),
+        3
+    );
+
+    let mut a = [0_u8; 4];
+    let mut b = [0_u8; 8];
+    let mut bufs = [IoSliceMut::new(&mut a), IoSliceMut::new(&mut b)];
+    assert_eq!(
+        check!(file.read_vectored_at_with_flags(&mut bufs, 0, RwFlags::HIPRI)),
+        12
+    );
+    assert_eq!(&a, b"abcd");
+    assert_eq!(&b, b"efghighi");
+}
+
+#[test]
+fn vectored_at_with_nowait() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = check!(OpenOptions::new()
+        .create_new(true)
+        .read(true)
+        .write(true)
+        .open(dir.path().join("file")));
+    check!(file.write_all_at(b"cached", 0));
+
+    // The data was just written, so it's in the page cache, and a `NOWAIT`
+    // read succeeds where it's supported.
+    let mut buf = [0_u8; 6];
+    match file.read_vectored_at_with_flags(&mut [IoSliceMut::new(&mut buf)], 0, RwFlags::NOWAIT) {
+        Ok(n) => {
+            assert_eq!(n, 6);
+            assert_eq!(&buf, b"cached");
+        }
+        Err(err) => assert!(
+            err.kind() == std::io::ErrorKind::Unsupported
+                || err.kind() == std::io::ErrorKind::WouldBlock,
+            "{}",
+            err
+        ),
+    }
+}
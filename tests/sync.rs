@@ -0,0 +1,56 @@
+#[macro_use]
+mod sys_common;
+
+use std::fs::OpenOptions;
+#[cfg(any(not(windows), feature = "cap_std_impls"))]
+use sys_common::io::tmpdir;
+use system_interface::fs::{FileIoExt, SyncRangeMode};
+
+#[test]
+fn sync_range() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = check!(OpenOptions::new()
+        .create_new(true)
+        .read(true)
+        .write(true)
+        .open(dir.path().join("file")));
+
+    check!(file.write_all_at(&[0xa5; 8192], 0));
+    check!(file.sync_range(0, 4096, SyncRangeMode::Start));
+    check!(file.sync_range(4096, 4096, SyncRangeMode::Wait));
+    check!(file.sync_range(0, 0, SyncRangeMode::Wait));
+    check!(FileIoExt::sync_data(&file));
+    check!(FileIoExt::sync_all(&file));
+}
+
+#[cfg(any(not(windows), feature = "cap_std_impls"))]
+#[test]
+fn cap_sync() {
+    let tmpdir = tmpdir();
+    let file = check!(tmpdir.open_with(
+        "file",
+        cap_std::fs::OpenOptions::new()
+            .create_new(true)
+            .read(true)
+            .write(true)
+    ));
+
+    check!(file.write_all_at(b"data", 0));
+    check!(file.sync_range(0, 4, SyncRangeMode::Wait));
+    check!(FileIoExt::sync_data(&file));
+    check!(FileIoExt::sync_all(&file));
+}
+
+#[cfg(not(windows))]
+#[test]
+fn sync_borrowed_fd() {
+    use rustix::fd::AsFd;
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = check!(std::fs::File::create(dir.path().join("file")));
+    let fd = file.as_fd();
+    check!(fd.write_all_at(b"data", 0));
+    check!(fd.sync_data());
+    check!(fd.sync_all());
+    check!(fd.sync_range(0, 4, SyncRangeMode::Wait));
+}
@@ -0,0 +1,84 @@
+#[macro_use]
+mod sys_common;
+
+use std::io::{ErrorKind, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+use system_interface::io::{IoTimeoutExt, TimedOut};
+
+fn timed_out_nbytes(err: &std::io::Error) -> usize {
+    assert_eq!(err.kind(), ErrorKind::TimedOut);
+    err.get_ref()
+        .and_then(|e| e.downcast_ref::<TimedOut>())
+        .unwrap()
+        .nbytes()
+}
+
+#[test]
+fn read_timeout_tcp_stream() {
+    let listener = check!(TcpListener::bind("127.0.0.1:0"));
+    let mut client = check!(TcpStream::connect(check!(listener.local_addr())));
+    let (server, _) = check!(listener.accept());
+
+    let mut buf = [0_u8; 8];
+    let err = server
+        .read_with_timeout(&mut buf, Duration::from_millis(10))
+        .unwrap_err();
+    assert_eq!(timed_out_nbytes(&err), 0);
+
+    check!(client.write_all(b"abc"));
+    assert_eq!(
+        check!(server.read_with_timeout(&mut buf, Duration::from_secs(10))),
+        3
+    );
+    assert_eq!(&buf[..3], b"abc");
+
+    // A partial read reports how much was read before the timeout.
+    check!(client.write_all(b"defg"));
+    let err = server
+        .read_exact_with_timeout(&mut buf, Duration::from_millis(100))
+        .unwrap_err();
+    assert_eq!(timed_out_nbytes(&err), 4);
+    assert_eq!(&buf[..4], b"defg");
+}
+
+#[test]
+fn write_timeout_tcp_stream() {
+    let listener = check!(TcpListener::bind("127.0.0.1:0"));
+    let client = check!(TcpStream::connect(check!(listener.local_addr())));
+    let (_server, _) = check!(listener.accept());
+
+    assert_ne!(
+        check!(client.write_with_timeout(b"hello", Duration::from_secs(10))),
+        0
+    );
+
+    // The peer never reads, so eventually the buffers fill up.
+    let buf = vec![0_u8; 64 << 20];
+    let err = client
+        .write_all_with_timeout(&buf, Duration::from_millis(100))
+        .unwrap_err();
+    let nbytes = timed_out_nbytes(&err);
+    assert!(nbytes > 0 && nbytes < buf.len());
+}
+
+#[cfg(unix)]
+#[test]
+fn read_timeout_pipe() {
+    use std::process::{Command, Stdio};
+
+    let mut child = check!(Command::new("sleep")
+        .arg("10")
+        .stdout(Stdio::piped())
+        .spawn());
+    let stdout = child.stdout.take().unwrap();
+
+    let mut buf = [0_u8; 8];
+    let err = stdout
+        .read_with_timeout(&mut buf, Duration::from_millis(10))
+        .unwrap_err();
+    assert_eq!(timed_out_nbytes(&err), 0);
+
+    check!(child.kill());
+    check!(child.wait());
+}
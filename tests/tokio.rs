@@ -0,0 +1,150 @@
+#![cfg(feature = "tokio_impls")]
+
+#[macro_use]
+mod sys_common;
+
+use std::future::Future;
+use std::io::{self, IoSliceMut};
+use std::time::Duration;
+use system_interface::fs::{AsyncFileIoExt, FdFlags, GetSetFdFlags};
+use system_interface::io::{AsyncPeek, AsyncReadReady, IsReadWrite, ReadReady};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+#[test]
+fn tokio_tcp_stream() {
+    block_on(async {
+        let listener = check!(TcpListener::bind("127.0.0.1:0").await);
+        let mut client = check!(TcpStream::connect(check!(listener.local_addr())).await);
+        let (mut server, _) = check!(listener.accept().await);
+
+        assert_eq!(check!(server.is_read_write()), (true, true));
+        assert_eq!(check!(server.num_ready_bytes()), 0);
+
+        let writer = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            check!(client.write_all(b"hello").await);
+            client
+        });
+
+        assert_ne!(check!(server.wait_ready().await), 0);
+        assert_ne!(check!(server.num_ready_bytes()), 0);
+        let mut buf = [0_u8; 8];
+        let n = check!(AsyncPeek::peek(&mut server, &mut buf).await);
+        assert_eq!(&buf[..n], &b"hello"[..n]);
+
+        let mut back = [0_u8; 5];
+        check!(server.read_exact(&mut back).await);
+        assert_eq!(&back, b"hello");
+
+        drop(check!(writer.await));
+        assert_eq!(check!(server.wait_ready().await), 0);
+    })
+}
+
+#[cfg(unix)]
+#[test]
+fn tokio_unix_stream() {
+    use tokio::net::UnixStream;
+
+    block_on(async {
+        let (mut a, mut b) = check!(UnixStream::pair());
+        assert_eq!(check!(a.is_read_write()), (true, true));
+        assert!(check!(a.get_fd_flags()).contains(FdFlags::NONBLOCK));
+
+        check!(b.write_all(b"hello").await);
+        assert_eq!(check!(a.wait_ready().await), 5);
+        assert_eq!(check!(a.num_ready_bytes()), 5);
+        let mut buf = [0_u8; 8];
+        assert_eq!(check!(AsyncPeek::peek(&mut a, &mut buf).await), 5);
+        assert_eq!(&buf[..5], b"hello");
+        assert_eq!(check!(a.read(&mut buf).await), 5);
+
+        drop(b);
+        assert_eq!(check!(a.wait_ready().await), 0);
+        assert_eq!(check!(AsyncPeek::peek(&mut a, &mut buf).await), 0);
+    })
+}
+
+#[cfg(unix)]
+#[test]
+fn tokio_pipe_and_child() {
+    use std::process::Stdio;
+    use tokio::net::unix::pipe;
+
+    block_on(async {
+        let (mut sender, receiver) = check!(pipe::pipe());
+        assert_eq!(check!(receiver.num_ready_bytes()), 0);
+        check!(sender.write_all(b"abc").await);
+        assert_eq!(check!(receiver.wait_ready().await), 3);
+
+        let mut child = check!(tokio::process::Command::new("echo")
+            .arg("hello")
+            .stdout(Stdio::piped())
+            .spawn());
+        let stdout = child.stdout.take().unwrap();
+        check!(child.wait().await);
+        assert_eq!(check!(stdout.num_ready_bytes()), 6);
+        assert_eq!(check!(stdout.is_read_write()), (true, false));
+    })
+}
+
+#[test]
+fn tokio_file() {
+    block_on(async {
+        let dir = tempfile::tempdir().unwrap();
+        let mut file = check!(
+            tokio::fs::OpenOptions::new()
+                .create_new(true)
+                .read(true)
+                .write(true)
+                .open(dir.path().join("file"))
+                .await
+        );
+        assert_eq!(check!(file.is_read_write()), (true, true));
+        assert!(!check!(file.get_fd_flags()).contains(FdFlags::APPEND));
+
+        check!(file.write_all(b"abcdefghijklmnopqrstuvwxyz").await);
+        check!(file.flush().await);
+        check!(file.rewind().await);
+        assert_eq!(check!(file.num_ready_bytes()), 26);
+
+        let mut buf = [0_u8; 4];
+        assert_eq!(check!(file.read_at(&mut buf, 4).await), 4);
+        assert_eq!(&buf, b"efgh");
+        assert_eq!(
+            file.read_exact_at(&mut buf, 24).await.unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+
+        let mut buf0 = vec![0; 8];
+        let mut buf1 = vec![0; 8];
+        let mut bufs = vec![IoSliceMut::new(&mut buf0), IoSliceMut::new(&mut buf1)];
+        check!(file.read_exact_vectored_at(&mut bufs, 4).await);
+        assert_eq!(&buf0, b"efghijkl");
+        assert_eq!(&buf1, b"mnopqrst");
+
+        check!(file.write_all_at(b"EFGH", 4).await);
+        let nwritten = check!(file.append(b"0123").await);
+        let mut back = Vec::new();
+        assert_eq!(
+            check!(file.read_to_end_at(&mut back, 0).await),
+            26 + nwritten
+        );
+        assert_eq!(&back[..8], b"abcdEFGH");
+
+        #[cfg(not(target_os = "openbsd"))]
+        {
+            check!(file.allocate(1024, 1024).await);
+            assert_eq!(check!(file.metadata().await).len(), 1024 + 1024);
+        }
+    })
+}
@@ -0,0 +1,81 @@
+#[macro_use]
+mod sys_common;
+
+use std::fs::OpenOptions;
+use system_interface::fs::FileIoExt;
+#[cfg(not(windows))]
+use system_interface::io::IoExt;
+
+fn source(dir: &std::path::Path, data: &[u8]) -> std::fs::File {
+    let file = check!(OpenOptions::new()
+        .create_new(true)
+        .read(true)
+        .write(true)
+        .open(dir.join("src")));
+    check!(file.write_all_at(data, 0));
+    file
+}
+
+#[test]
+fn transfer_to_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = source(dir.path(), b"abcdefghijklmnopqrstuvwxyz");
+    let dst = check!(OpenOptions::new()
+        .create_new(true)
+        .read(true)
+        .write(true)
+        .open(dir.path().join("dst")));
+
+    assert_eq!(check!(src.transfer_to(&dst, 2, 5)), 5);
+    assert_eq!(check!(src.transfer_to(&dst, 20, 100)), 6);
+    assert_eq!(check!(src.stream_position()), 0);
+
+    let mut buf = String::new();
+    check!(dst.read_to_string_at(&mut buf, 0));
+    assert_eq!(buf, "cdefguvwxyz");
+}
+
+#[cfg(not(windows))]
+#[test]
+fn transfer_to_socket_and_pipe() {
+    use std::os::unix::net::UnixStream;
+
+    let dir = tempfile::tempdir().unwrap();
+    let src = source(dir.path(), b"hello, world");
+
+    let (a, b) = check!(UnixStream::pair());
+    assert_eq!(check!(a.transfer_from(&src, 7, 5)), 5);
+    let mut buf = [0_u8; 5];
+    check!(b.read_exact(&mut buf));
+    assert_eq!(&buf, b"world");
+
+    let (reader, writer) = check!(rustix::pipe::pipe());
+    assert_eq!(check!(src.transfer_to(&writer, 0, 5)), 5);
+    check!(reader.read_exact(&mut buf));
+    assert_eq!(&buf, b"hello");
+}
+
+#[cfg(not(windows))]
+#[test]
+fn transfer_nonblocking() {
+    use std::os::unix::net::UnixStream;
+
+    let dir = tempfile::tempdir().unwrap();
+    let data = vec![0xa5; 8 << 20];
+    let src = source(dir.path(), &data);
+
+    let (a, _b) = check!(UnixStream::pair());
+    check!(a.set_nonblocking(true));
+
+    // The socket buffer fills up, so only part of the data is transferred.
+    let n = check!(a.transfer_from(&src, 0, data.len() as u64));
+    assert!(n > 0 && n < data.len() as u64);
+
+    // Now nothing can be transferred.
+    assert_eq!(
+        a.transfer_from(&src, n, data.len() as u64 - n)
+            .unwrap_err()
+            .kind(),
+        std::io::ErrorKind::WouldBlock
+    );
+}
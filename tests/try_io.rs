@@ -0,0 +1,83 @@
+#[macro_use]
+mod sys_common;
+
+use std::io::{ErrorKind, IoSliceMut};
+use std::net::{TcpListener, TcpStream};
+use system_interface::io::IoExt;
+
+#[test]
+fn try_read_tcp_stream() {
+    let listener = check!(TcpListener::bind("127.0.0.1:0"));
+    let client = check!(TcpStream::connect(check!(listener.local_addr())));
+    let (server, _) = check!(listener.accept());
+
+    let mut buf = [0_u8; 8];
+    assert_eq!(
+        server.try_read(&mut buf).unwrap_err().kind(),
+        ErrorKind::WouldBlock
+    );
+
+    check!(client.write_all(b"hello"));
+    check!(client.flush());
+    let mut nread = 0;
+    while nread < 5 {
+        match server.try_read(&mut buf[nread..]) {
+            Ok(n) => nread += n,
+            Err(err) if err.kind() == ErrorKind::WouldBlock => std::thread::yield_now(),
+            Err(err) => panic!("{}", err),
+        }
+    }
+    assert_eq!(&buf[..5], b"hello");
+
+    assert_eq!(check!(client.try_write(b"world")), 5);
+    let (mut a, mut b) = ([0_u8; 2], [0_u8; 8]);
+    let mut nread = 0;
+    while nread < 5 {
+        let mut bufs = [IoSliceMut::new(&mut a), IoSliceMut::new(&mut b)];
+        match server.try_read_vectored(&mut bufs) {
+            Ok(n) => nread += n,
+            Err(err) if err.kind() == ErrorKind::WouldBlock => std::thread::yield_now(),
+            Err(err) => panic!("{}", err),
+        }
+    }
+    assert_eq!(&a, b"wo");
+    assert_eq!(&b[..3], b"rld");
+}
+
+#[cfg(unix)]
+#[test]
+fn try_read_pipe_leaves_flags_unchanged() {
+    use std::process::{Command, Stdio};
+    use system_interface::fs::{FdFlags, GetSetFdFlags};
+
+    let mut child = check!(Command::new("sleep")
+        .arg("10")
+        .stdout(Stdio::piped())
+        .spawn());
+    let stdout = child.stdout.take().unwrap();
+
+    let mut buf = [0_u8; 8];
+    assert_eq!(
+        stdout.try_read(&mut buf).unwrap_err().kind(),
+        ErrorKind::WouldBlock
+    );
+    assert!(!check!(stdout.get_fd_flags()).contains(FdFlags::NONBLOCK));
+
+    check!(child.kill());
+    check!(child.wait());
+}
+
+#[cfg(unix)]
+#[test]
+fn try_read_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file");
+    check!(std::fs::write(&path, b"hello"));
+    let file = check!(std::fs::File::open(&path));
+
+    // The data was just written, so it's in the page cache.
+    let mut buf = [0_u8; 8];
+    assert_eq!(check!(file.try_read(&mut buf)), 5);
+    assert_eq!(&buf[..5], b"hello");
+    assert_eq!(check!(file.try_read(&mut buf)), 0);
+}
@@ -626,3 +626,34 @@ fn write() {
     assert!(nwritten0 <= 8);
     assert!(nwritten1 <= 8);
 }
+
+/// More buffers than `IOV_MAX` are split across several system calls.
+#[test]
+fn many_buffers_vectored_at() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = check!(OpenOptions::new()
+        .create_new(true)
+        .read(true)
+        .write(true)
+        .open(dir.path().join("file")));
+
+    let data: Vec<u8> = (0..3000_u32).map(|i| (i % 251) as u8).collect();
+    let mut bufs: Vec<IoSlice> = data.chunks(1).map(IoSlice::new).collect();
+    check!(file.write_all_vectored_at(&mut bufs, 0));
+
+    let mut back = vec![0_u8; 3000];
+    let mut bufs: Vec<IoSliceMut> = back.chunks_mut(1).map(IoSliceMut::new).collect();
+    check!(file.read_exact_vectored_at(&mut bufs, 0));
+    assert_eq!(back, data);
+
+    // A long run of empty buffers isn't mistaken for EOF.
+    let mut empties = [[0_u8; 0]; 2000];
+    let mut last = [0_u8; 4];
+    let mut bufs: Vec<IoSliceMut> = empties
+        .iter_mut()
+        .map(|buf| IoSliceMut::new(buf))
+        .chain(Some(IoSliceMut::new(&mut last)))
+        .collect();
+    check!(file.read_exact_vectored_at(&mut bufs, 0));
+    assert_eq!(last, data[..4]);
+}